@@ -1,6 +1,26 @@
 // SPDX-License-Identifier: BSD-2-Clause-Patent
 
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+
+use anyhow::anyhow;
+use reqwest::blocking::Client;
+use serde::{Deserialize, Serialize};
+use strum::IntoEnumIterator;
 use strum_macros::{Display, EnumIter, EnumString};
+use tracing::{info, warn};
+
+use crate::tag::fuzzy_score;
+
+const FETCH_TIMEOUT: Duration = Duration::from_secs(10);
+// Name of the category `<select>` of the upload form
+const CATEGORY_SELECT_NAME: &str = "type";
+// Snapshot of the upload form's categories, in the format of the cache; refresh it by copying
+// categories.json from the cache folder after a successful sync
+const BUNDLED_CATEGORIES: &str = include_str!("../res/categories.json");
 
 #[derive(EnumIter, EnumString, Display, Debug, Copy, Clone, Eq, PartialEq)]
 pub enum Category {
@@ -94,4 +114,300 @@ pub enum Category {
     WrestlingAndSports,
     #[strum(serialize = "Youngblood")]
     Youngblood
-}
\ No newline at end of file
+}
+
+/// Order `categories` by how many uploads used them (by name), most used first; ties keep
+/// their original order
+pub fn sort_by_usage(categories: &mut [Category], usage: &BTreeMap<String, usize>) {
+    categories.sort_by_key(|category| {
+        Reverse(usage.get(&category.to_string()).copied().unwrap_or_default())
+    });
+}
+
+/// Split the categories offered by the site whose names match `query` into the favourites and
+/// the others, the latter ordered by usage; `excluded` categories are left out
+pub fn filter_categories(
+    query: &str,
+    site_categories: &SiteCategories,
+    excluded: &[Category],
+    favourites: &[String],
+    usage: &BTreeMap<String, usize>,
+) -> (Vec<Category>, Vec<Category>) {
+    let (favourites, mut others): (Vec<Category>, Vec<Category>) = Category::iter()
+        .filter(|category| {
+            *category != Category::None
+                && site_categories.offers(*category)
+                && !excluded.contains(category)
+                && fuzzy_score(query, &category.to_string()).is_some()
+        })
+        .partition(|category| favourites.contains(&category.to_string()));
+    sort_by_usage(&mut others, usage);
+    (favourites, others)
+}
+
+/// Return the category picked by pressing Enter after typing `query`: the first favourite, or
+/// else the most used match
+pub fn top_match(query: &str, favourites: &[Category], others: &[Category]) -> Option<Category> {
+    if query.is_empty() {
+        return None;
+    }
+    favourites.first().or(others.first()).copied()
+}
+
+/// A category as offered by the site's upload form, with the value submitted for it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SiteCategory {
+    pub id: u32,
+    pub name: String,
+}
+
+/// The site's current list of categories, used to map a `Category` to its form value
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SiteCategories {
+    categories: Vec<SiteCategory>,
+    // Whether `categories` came from the site, live or cached; form values cannot be guessed
+    is_available: bool,
+}
+
+impl SiteCategories {
+    /// Stand-in when there is neither a cached nor a bundled list; every category can be chosen
+    /// but none has a form value
+    pub fn unavailable() -> Self {
+        Self {
+            categories: Vec::new(),
+            is_available: false,
+        }
+    }
+
+    fn new(categories: Vec<SiteCategory>) -> Self {
+        Self {
+            categories,
+            is_available: true,
+        }
+    }
+
+    /// Return the cached list at `path`, or else the bundled one, or else an unavailable one
+    pub fn load_cached<P: AsRef<Path>>(path: P) -> Self {
+        Self::load(path)
+            .or_else(Self::bundled)
+            .unwrap_or_else(Self::unavailable)
+    }
+
+    /// Return the snapshot shipped with the application, unless it is empty
+    fn bundled() -> Option<Self> {
+        match serde_json::from_str::<Vec<SiteCategory>>(BUNDLED_CATEGORIES) {
+            Ok(categories) if !categories.is_empty() => {
+                info!("Using the bundled categories");
+                Some(Self::new(categories))
+            }
+            Ok(_) => None,
+            Err(err) => {
+                warn!(?err, "Unable to deserialise the bundled categories");
+                None
+            }
+        }
+    }
+
+    /// Fetch the live list from the upload page and update the cache at `path`
+    pub fn sync<P: AsRef<Path>>(client: &Client, url: &str, path: P) -> anyhow::Result<Self> {
+        let categories = Self::fetch(client, url)?;
+        categories.save(path);
+        Ok(categories)
+    }
+
+    fn fetch(client: &Client, url: &str) -> anyhow::Result<Self> {
+        let html = client
+            .get(url)
+            .timeout(FETCH_TIMEOUT)
+            .send()?
+            .error_for_status()?
+            .text()?;
+        let categories = Self::parse(&html)?;
+
+        let unsupported = categories.unsupported();
+        if !unsupported.is_empty() {
+            info!(?unsupported, "Categories on the site which cannot be selected yet");
+        }
+        info!("Fetched {} categories", categories.categories.len());
+        Ok(categories)
+    }
+
+    /// Parse the options of the category `<select>` of the upload form
+    fn parse(html: &str) -> anyhow::Result<Self> {
+        let select_start = html
+            .match_indices("<select")
+            .map(|(start, _)| start)
+            .find(|&start| {
+                html[start..].split('>').next().is_some_and(|tag| {
+                    tag.contains(&format!("name=\"{CATEGORY_SELECT_NAME}\""))
+                        || tag.contains(&format!("name='{CATEGORY_SELECT_NAME}'"))
+                })
+            })
+            .ok_or_else(|| anyhow!("No category list found on the upload page"))?;
+        let select = &html[select_start..];
+        let select = &select[..select.find("</select>").unwrap_or(select.len())];
+
+        let categories: Vec<SiteCategory> = select
+            .split("<option")
+            .skip(1)
+            .filter_map(|option| {
+                let (attributes, rest) = option.split_once('>')?;
+                let value = attributes
+                    .split_once("value=")?
+                    .1
+                    .trim_start_matches(['"', '\''])
+                    .split(['"', '\'', ' '])
+                    .next()?;
+                let name = rest.split('<').next()?;
+                Some(SiteCategory {
+                    id: value.parse().ok().filter(|id| *id != 0)?,
+                    name: decode_entities(name.trim()),
+                })
+            })
+            .collect();
+        if categories.is_empty() {
+            return Err(anyhow!("The category list on the upload page is empty"));
+        }
+        Ok(Self::new(categories))
+    }
+
+    fn load<P: AsRef<Path>>(path: P) -> Option<Self> {
+        let file_content = match fs::read_to_string(path.as_ref()) {
+            Ok(string) => string,
+            Err(err) => {
+                warn!(?err, "Unable to load cached categories; IGNORE this warning if initialising");
+                return None;
+            }
+        };
+
+        match serde_json::from_str::<Vec<SiteCategory>>(&file_content) {
+            Ok(categories) => {
+                info!("Loaded cached categories");
+                Some(Self::new(categories))
+            }
+            Err(err) => {
+                warn!(?err, "Unable to deserialise cached categories");
+                None
+            }
+        }
+    }
+
+    fn save<P: AsRef<Path>>(&self, path: P) {
+        let Ok(categories) = serde_json::to_string(&self.categories) else {
+            warn!("Unable to serialise or hence save categories; saving aborted");
+            return;
+        };
+
+        match fs::write(path, categories) {
+            Ok(()) => info!("Saved categories"),
+            Err(err) => warn!(?err, "Unable to save categories; saving aborted"),
+        }
+    }
+
+    fn to_category(name: &str) -> Option<Category> {
+        Category::iter().find(|category| category.to_string().eq_ignore_ascii_case(name))
+    }
+
+    pub fn is_available(&self) -> bool {
+        self.is_available
+    }
+
+    /// Return whether `category` can be chosen; all of them can while the list is unavailable
+    pub fn offers(&self, category: Category) -> bool {
+        !self.is_available || self.id_of(category).is_some()
+    }
+
+    /// Return the form value of `category`, or `None` if the site does not offer it or the list
+    /// is unavailable
+    pub fn id_of(&self, category: Category) -> Option<u32> {
+        let name = category.to_string();
+        self.categories
+            .iter()
+            .find(|site_category| site_category.name.eq_ignore_ascii_case(&name))
+            .map(|site_category| site_category.id)
+    }
+
+    /// Return the names of the categories on the site which have no `Category` yet
+    pub fn unsupported(&self) -> Vec<&str> {
+        self.categories
+            .iter()
+            .map(|category| category.name.as_str())
+            .filter(|name| Self::to_category(name).is_none())
+            .collect()
+    }
+}
+
+fn decode_entities(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&amp;", "&")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sort_by_usage() {
+        let mut categories = [Category::Amateur, Category::Anal, Category::Bears, Category::Solo];
+        let usage = BTreeMap::from([("Solo".to_owned(), 3), ("Anal".to_owned(), 1)]);
+        sort_by_usage(&mut categories, &usage);
+        assert_eq!(
+            categories,
+            [Category::Solo, Category::Anal, Category::Amateur, Category::Bears]
+        );
+    }
+
+    #[test]
+    fn test_top_match() {
+        let site_categories = SiteCategories::unavailable();
+        let usage = BTreeMap::from([("Bisexual".to_owned(), 2), ("Black".to_owned(), 5)]);
+        let top_match = |query: &str, excluded: &[Category], favourites: &[String]| {
+            let (favourites, others) =
+                filter_categories(query, &site_categories, excluded, favourites, &usage);
+            top_match(query, &favourites, &others)
+        };
+
+        assert_eq!(top_match("", &[], &[]), None);
+        assert_eq!(top_match("xyz", &[], &[]), None);
+        assert_eq!(top_match("bi", &[], &[]), Some(Category::Bisexual));
+        assert_eq!(top_match("b", &[], &[]), Some(Category::Black));
+        assert_eq!(top_match("b", &[Category::Black], &[]), Some(Category::Bisexual));
+        assert_eq!(
+            top_match("b", &[], &["Bears".to_owned()]),
+            Some(Category::Bears)
+        );
+    }
+
+    #[test]
+    fn test_parse() {
+        let html = r#"<form><select name="sort"><option value="1">Newest</option></select>
+            <select name="type" id="category">
+                <option value="0">(choose one)</option>
+                <option value="12">Amateur</option>
+                <option value='7' selected>Books &amp; Magazines</option>
+                <option value="99">Brand New Category</option>
+            </select></form>"#;
+        let categories = SiteCategories::parse(html).unwrap();
+        assert_eq!(categories.categories.len(), 3);
+        assert_eq!(categories.id_of(Category::Amateur), Some(12));
+        assert_eq!(categories.id_of(Category::BooksMagazines), Some(7));
+        assert_eq!(categories.id_of(Category::Anal), None);
+        assert!(!categories.offers(Category::Anal));
+        assert_eq!(categories.unsupported(), ["Brand New Category"]);
+
+        assert!(SiteCategories::parse("<html>Please log in</html>").is_err());
+        let unavailable = SiteCategories::unavailable();
+        assert!(unavailable.offers(Category::Anal));
+        assert_eq!(unavailable.id_of(Category::Anal), None);
+
+        // Without a cache, the bundled list is used if there is one
+        let dir = tempfile::tempdir().unwrap();
+        let loaded = SiteCategories::load_cached(dir.path().join("categories.json"));
+        assert_eq!(loaded, SiteCategories::bundled().unwrap_or_else(SiteCategories::unavailable));
+        categories.save(dir.path().join("categories.json"));
+        assert_eq!(SiteCategories::load_cached(dir.path().join("categories.json")), categories);
+    }
+}
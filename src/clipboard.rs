@@ -0,0 +1,112 @@
+// SPDX-License-Identifier: BSD-2-Clause-Patent
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use arboard::Clipboard;
+use image::RgbaImage;
+use reqwest::Url;
+use tracing::{info, warn};
+
+pub enum Pasted {
+    Image(PathBuf),
+    Paths(Vec<PathBuf>),
+    Nothing,
+}
+
+/// Read the clipboard, saving image data as a PNG in `dir`
+pub fn paste<P: AsRef<Path>>(dir: P) -> anyhow::Result<Pasted> {
+    let mut clipboard = Clipboard::new()?;
+    if let Ok(image) = clipboard.get_image() {
+        let image = RgbaImage::from_raw(
+            image.width as u32,
+            image.height as u32,
+            image.bytes.into_owned(),
+        )
+        .ok_or_else(|| anyhow::anyhow!("The image on the clipboard is malformed"))?;
+        fs::create_dir_all(dir.as_ref())?;
+        let time = SystemTime::now().duration_since(UNIX_EPOCH)?.as_millis();
+        let path = dir.as_ref().join(format!("pasted-{time}.png"));
+        image.save(&path)?;
+        info!("Saved the pasted image to {}", path.to_string_lossy());
+        return Ok(Pasted::Image(path));
+    }
+
+    // File managers copy files as a list of URIs, which is also offered as text on some
+    // platforms; other text is ignored so that copying a path does not replace the content
+    let paths = match clipboard.get().file_list() {
+        Ok(paths) => paths,
+        Err(_) => parse_file_uris(&clipboard.get_text().unwrap_or_default()),
+    };
+    let paths: Vec<PathBuf> = paths.into_iter().filter(|path| path.exists()).collect();
+    Ok(if paths.is_empty() {
+        Pasted::Nothing
+    } else {
+        Pasted::Paths(paths)
+    })
+}
+
+/// Parse the `file://` URIs of a `text/uri-list`, one per line
+fn parse_file_uris(text: &str) -> Vec<PathBuf> {
+    text.lines()
+        .map(str::trim)
+        .filter(|line| line.starts_with("file://"))
+        .filter_map(|line| Url::parse(line).ok()?.to_file_path().ok())
+        .collect()
+}
+
+/// Delete the folders of pasted images in `root` left by previous sessions, except `current` and
+/// those holding any of the `referenced` paths, e.g. images of a draft or a queued upload
+pub fn remove_stale_paste_dirs(root: &Path, current: &Path, referenced: &[PathBuf]) {
+    let Ok(entries) = fs::read_dir(root) else {
+        // Nothing has been pasted yet
+        return;
+    };
+    for dir in entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.is_dir() && path != current)
+    {
+        if referenced.iter().any(|path| path.starts_with(&dir)) {
+            continue;
+        }
+        match fs::remove_dir_all(&dir) {
+            Ok(()) => info!("Removed pasted images {}", dir.to_string_lossy()),
+            Err(err) => warn!(?err, "Unable to remove pasted images {}", dir.to_string_lossy()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[cfg(unix)]
+    fn test_parse_file_uris() {
+        assert_eq!(
+            parse_file_uris("# copied\r\nfile:///home/user/My%20Videos/clip.mp4\n\n/tmp/a.png \n"),
+            [PathBuf::from("/home/user/My Videos/clip.mp4")]
+        );
+        assert!(parse_file_uris("/").is_empty());
+        assert!(parse_file_uris("src").is_empty());
+    }
+
+    #[test]
+    fn test_remove_stale_paste_dirs() {
+        let root = tempfile::tempdir().unwrap();
+        let dir = |name: &str| {
+            let dir = root.path().join(name);
+            fs::create_dir_all(&dir).unwrap();
+            fs::write(dir.join("pasted.png"), b"").unwrap();
+            dir
+        };
+        let (stale, drafted, current) = (dir("1"), dir("2"), dir("3"));
+
+        remove_stale_paste_dirs(root.path(), &current, &[drafted.join("pasted.png")]);
+        assert!(!stale.exists());
+        assert!(drafted.exists());
+        assert!(current.exists());
+    }
+}
@@ -0,0 +1,200 @@
+// SPDX-License-Identifier: BSD-2-Clause-Patent
+
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use tracing::{debug, info, warn};
+
+use crate::cache_dir;
+use crate::tag::TagData;
+
+pub const AUTOSAVE_INTERVAL: Duration = Duration::from_secs(5);
+
+pub fn draft_path(name: &str) -> PathBuf {
+    cache_dir("drafts").join(format!("{name}.json"))
+}
+
+/// Serialisable snapshot of the upload form
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Draft {
+    pub is_file: bool,
+    pub content: Option<PathBuf>,
+    // Stored by display name so that renamed or removed categories do not break deserialisation
+    pub categories: Vec<String>,
+    pub images: Vec<PathBuf>,
+    // The URLs of the images already uploaded to the image host
+    #[serde(default)]
+    pub image_urls: BTreeMap<PathBuf, String>,
+    pub title: String,
+    pub description: String,
+    pub tags: Vec<String>,
+    pub custom_tags: Vec<TagData>,
+}
+
+impl Draft {
+    pub fn generate_name() -> String {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        format!("draft-{timestamp}")
+    }
+
+    /// Keep only characters that are safe to use in a filename on every platform
+    pub fn sanitise_name(name: &str) -> String {
+        name.trim()
+            .chars()
+            .map(|c| {
+                if c.is_alphanumeric() || c == '-' || c == '_' || c == ' ' {
+                    c
+                } else {
+                    '_'
+                }
+            })
+            .collect()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.content.is_none()
+            && self.categories.is_empty()
+            && self.images.is_empty()
+            && self.title.trim().is_empty()
+            && self.description.trim().is_empty()
+            && self.tags.is_empty()
+            && self.custom_tags.is_empty()
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Option<Self> {
+        let file_content = match fs::read_to_string(path.as_ref()) {
+            Ok(string) => string,
+            Err(err) => {
+                warn!(?err, "Unable to load the draft");
+                return None;
+            }
+        };
+
+        match serde_json::from_str::<Self>(&file_content) {
+            Ok(draft) => {
+                info!("Loaded draft {}", path.as_ref().to_string_lossy());
+                Some(draft)
+            }
+            Err(err) => {
+                warn!(?err, "Unable to deserialise the draft");
+                None
+            }
+        }
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) {
+        let Ok(draft) = serde_json::to_string_pretty(self) else {
+            warn!(?self, "Unable to serialise or hence save the draft; saving aborted");
+            return;
+        };
+
+        match fs::write(path.as_ref(), draft) {
+            // Saved on every autosave tick
+            Ok(()) => debug!("Saved draft {}", path.as_ref().to_string_lossy()),
+            Err(err) => warn!(?err, "Unable to save the draft; saving aborted"),
+        }
+    }
+
+    /// Return the names of all drafts in `dir`, most recently modified first
+    pub fn list<P: AsRef<Path>>(dir: P) -> Vec<String> {
+        let entries = match fs::read_dir(dir.as_ref()) {
+            Ok(entries) => entries,
+            Err(err) => {
+                warn!(?err, "Unable to read the drafts folder");
+                return Vec::new();
+            }
+        };
+
+        let mut drafts: Vec<(SystemTime, String)> = entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+            .filter_map(|path| {
+                let modified = path.metadata().and_then(|m| m.modified()).ok()?;
+                let name = path.file_stem()?.to_string_lossy().into_owned();
+                Some((modified, name))
+            })
+            .collect();
+        drafts.sort_by_key(|(modified, _)| Reverse(*modified));
+        drafts.into_iter().map(|(_, name)| name).collect()
+    }
+
+    pub fn delete<P: AsRef<Path>>(path: P) {
+        match fs::remove_file(path.as_ref()) {
+            Ok(()) => info!("Deleted draft {}", path.as_ref().to_string_lossy()),
+            Err(err) => warn!(?err, "Unable to delete the draft"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::File;
+
+    use super::*;
+
+    #[test]
+    fn test_names() {
+        assert!(Draft::generate_name().starts_with("draft-"));
+        assert_eq!(Draft::sanitise_name("  My draft: v2/final "), "My draft_ v2_final");
+        assert_eq!(Draft::sanitise_name("../.."), "_____");
+    }
+
+    #[test]
+    fn test_is_empty() {
+        assert!(Draft::default().is_empty());
+        let draft = Draft {
+            title: "  ".to_owned(),
+            ..Default::default()
+        };
+        assert!(draft.is_empty());
+        let draft = Draft {
+            tags: vec!["Solo".to_owned()],
+            ..Default::default()
+        };
+        assert!(!draft.is_empty());
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let dir = dir.path();
+        let draft = Draft {
+            content: Some(PathBuf::from("/videos/holiday")),
+            categories: vec!["Amateur".to_owned()],
+            images: vec![PathBuf::from("/videos/holiday.png")],
+            image_urls: BTreeMap::from([(
+                PathBuf::from("/videos/holiday.png"),
+                "https://images.example/1.png".to_owned(),
+            )]),
+            title: "Holiday".to_owned(),
+            ..Default::default()
+        };
+        draft.save(dir.join("old.json"));
+        draft.save(dir.join("new.json"));
+        fs::write(dir.join("notes.txt"), "not a draft").unwrap();
+        let an_hour_ago = SystemTime::now() - Duration::from_secs(3600);
+        File::options()
+            .write(true)
+            .open(dir.join("old.json"))
+            .unwrap()
+            .set_modified(an_hour_ago)
+            .unwrap();
+
+        assert_eq!(Draft::load(dir.join("new.json")), Some(draft));
+        assert_eq!(Draft::list(dir), ["new", "old"]);
+        Draft::delete(dir.join("new.json"));
+        assert_eq!(Draft::list(dir), ["old"]);
+        assert_eq!(Draft::load(dir.join("new.json")), None);
+
+        fs::write(dir.join("corrupt.json"), "{").unwrap();
+        assert_eq!(Draft::load(dir.join("corrupt.json")), None);
+    }
+}
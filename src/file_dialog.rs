@@ -1,14 +1,11 @@
 // SPDX-License-Identifier: BSD-2-Clause-Patent
 
-use std::borrow::Cow;
 use std::path::{Path, PathBuf};
-use std::sync::mpsc;
 
-use eframe::egui::{ColorImage, TextureHandle, TextureOptions, Ui};
+use eframe::egui::{Context, Ui};
 use rfd::FileDialog;
-use tracing::warn;
 
-use crate::DialogMessage;
+use crate::image::{ImageLoader, IMAGE_EXTENSIONS};
 
 use super::Image;
 
@@ -45,111 +42,72 @@ fn create_file_dialog<P: AsRef<Path> + Clone>(default_directory: Option<P>) -> F
 pub fn select_content<P: AsRef<Path> + Clone>(
     is_file: bool,
     default_directory: Option<P>,
-) -> Option<(PathBuf, String, u64)> {
+) -> Option<PathBuf> {
     create_file_dialog(default_directory)
         .pred(|_| is_file, FileDialog::pick_file, FileDialog::pick_folder)
-        .map(|c| {
-            (
-                c.clone(),
-                c.to_string_lossy().into_owned(),
-                fs_extra::dir::get_size(&c).unwrap(),
-            )
-        })
 }
 
-// TODO: Add all supported file extensions
+/// Return the path, displayed path and total size of the content at `path`
+///
+/// Walks the whole content, so it should not be called on the UI thread for arbitrary paths.
+pub fn content_from_path(path: PathBuf) -> anyhow::Result<(PathBuf, String, u64)> {
+    let path_string = path.to_string_lossy().into_owned();
+    let size = fs_extra::dir::get_size(&path)?;
+    Ok((path, path_string, size))
+}
+
+pub fn select_tag_file<P: AsRef<Path> + Clone>(
+    is_saving: bool,
+    default_directory: Option<P>,
+) -> Option<PathBuf> {
+    create_file_dialog(default_directory)
+        .add_filter("JSON", &["json"])
+        .set_file_name("custom_tags.json")
+        .pred(|_| is_saving, FileDialog::save_file, FileDialog::pick_file)
+}
+
 pub(crate) fn select_images<P: AsRef<Path> + Clone>(
     default_directory: Option<P>,
     current_images: &[Image],
-    sender: &mpsc::Sender<DialogMessage>,
+    image_loader: &ImageLoader,
     ui: &mut Ui,
-) -> Option<Vec<Image>> {
+) -> Option<(Vec<Image>, Vec<PathBuf>)> {
+    // File dialogs may match extensions case-sensitively
+    let extensions: Vec<String> = IMAGE_EXTENSIONS
+        .iter()
+        .flat_map(|extension| [extension.to_string(), extension.to_uppercase()])
+        .collect();
+    let extensions: Vec<&str> = extensions.iter().map(String::as_str).collect();
     let Some(image_paths) = create_file_dialog(default_directory)
-        .add_filter(
-            "image",
-            &["png", "PNG", "jpg", "JPG", "jpeg", "JPEG", "gif", "GIF"],
-        )
+        .add_filter("image", &extensions)
         .pick_files() else {
         return None;
     };
 
+    Some(load_new_images(&image_paths, current_images, image_loader, ui.ctx()))
+}
+
+/// Start loading the images which are not already in `current_images`, returning them with the
+/// paths of the ones skipped
+pub(crate) fn load_new_images(
+    image_paths: &[PathBuf],
+    current_images: &[Image],
+    image_loader: &ImageLoader,
+    ctx: &Context,
+) -> (Vec<Image>, Vec<PathBuf>) {
     let current_image_paths: Vec<&Path> = current_images
         .iter()
         .map(|image| image.path.as_path())
         .collect();
 
-    let mut duplicate_image_filenames = Vec::new();
-
-    let images = Some(
-        image_paths
-            .iter()
-            .filter(|image_path| {
-                if current_image_paths.contains(&(image_path.as_ref())) {
-                    duplicate_image_filenames.push(
-                        image_path
-                            .file_name()
-                            .unwrap()
-                            .to_string_lossy()
-                            .into_owned(),
-                    );
-                    false
-                } else {
-                    true
-                }
-            })
-            .map(|image_path| Image {
-                path: image_path.clone(),
-                filename: image_path
-                    .file_name()
-                    .unwrap()
-                    .to_string_lossy()
-                    .into_owned(),
-                size: image_path.metadata().unwrap().len(),
-                texture_handle: {
-                    // TODO: Add multi-threaded texture loading support for many large image files
-                    match create_image_texture_handle(image_path, ui) {
-                        Ok(th) => Some(th),
-                        Err(err) => {
-                            warn!(
-                                ?err,
-                                "Unable to load image as texture; image preview unavailable"
-                            );
-                            None
-                        }
-                    }
-                },
-            })
-            .collect(),
-    );
-    if !duplicate_image_filenames.is_empty() {
-        sender
-            .send(DialogMessage(
-                Cow::Owned(format!("Duplicate image:\n\n{}", {
-                    duplicate_image_filenames
-                        .into_iter()
-                        .collect::<Vec<String>>()
-                        .join("\n")
-                })),
-                true,
-            ))
-            .unwrap();
-    }
-    images
-}
-
-fn create_image_texture_handle<P: AsRef<Path>>(
-    image_path: P,
-    ui: &mut Ui,
-) -> anyhow::Result<TextureHandle> {
-    let image = image::io::Reader::open(image_path.as_ref())?.decode()?;
-    let size = [image.width() as _, image.height() as _];
-    let image_buffer = image.to_rgba8();
-    let pixels = image_buffer.as_flat_samples();
+    let (duplicate_paths, new_paths): (Vec<PathBuf>, Vec<PathBuf>) = image_paths
+        .iter()
+        .cloned()
+        .partition(|image_path| current_image_paths.contains(&image_path.as_path()));
 
-    let colour_image = ColorImage::from_rgba_unmultiplied(size, pixels.as_slice());
-    Ok(ui.ctx().load_texture(
-        image_path.as_ref().to_string_lossy(),
-        colour_image,
-        TextureOptions::LINEAR,
-    ))
+    let images = new_paths
+        .iter()
+        .map(|image_path| image_loader.load(image_path, ctx))
+        .collect();
+    (images, duplicate_paths)
 }
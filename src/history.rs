@@ -0,0 +1,78 @@
+// SPDX-License-Identifier: BSD-2-Clause-Patent
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+/// A record of a submitted upload
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    // Seconds since the Unix epoch
+    pub time: u64,
+    pub content: PathBuf,
+    pub title: String,
+    // Stored by display name, like in drafts
+    pub categories: Vec<String>,
+    pub tags: Vec<String>,
+}
+
+impl HistoryEntry {
+    pub fn new(content: PathBuf, title: String, categories: Vec<String>, tags: Vec<String>) -> Self {
+        Self {
+            time: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_secs(),
+            content,
+            title,
+            categories,
+            tags,
+        }
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Vec<Self> {
+        let file_content = match fs::read_to_string(path.as_ref()) {
+            Ok(string) => string,
+            Err(err) => {
+                warn!(?err, "Unable to load the upload history; IGNORE this warning if initialising");
+                return Vec::new();
+            }
+        };
+
+        match serde_json::from_str::<Vec<Self>>(&file_content) {
+            Ok(history) => {
+                info!("Loaded the upload history");
+                history
+            }
+            Err(err) => {
+                warn!(?err, "Unable to deserialise the upload history");
+                Vec::new()
+            }
+        }
+    }
+
+    pub fn save<P: AsRef<Path>>(path: P, history: &[Self]) {
+        let Ok(history) = serde_json::to_string_pretty(history) else {
+            warn!("Unable to serialise or hence save the upload history; saving aborted");
+            return;
+        };
+
+        match fs::write(path, history) {
+            Ok(()) => info!("Saved the upload history"),
+            Err(err) => warn!(?err, "Unable to save the upload history; saving aborted"),
+        }
+    }
+}
+
+/// Count how many uploads in `history` used each category
+pub fn category_usage(history: &[HistoryEntry]) -> BTreeMap<String, usize> {
+    let mut usage = BTreeMap::new();
+    for category in history.iter().flat_map(|entry| entry.categories.iter()) {
+        *usage.entry(category.clone()).or_default() += 1;
+    }
+    usage
+}
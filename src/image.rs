@@ -1,18 +1,82 @@
 // SPDX-License-Identifier: BSD-2-Clause-Patent
 
+use std::fs;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, ErrorKind};
 use std::path::{Path, PathBuf};
+use std::process::{Command, Output, Stdio};
+use std::sync::mpsc;
+use std::time::{Duration, UNIX_EPOCH};
 
+use ab_glyph::{point, Font, FontVec, PxScale, ScaleFont};
+use anyhow::{anyhow, ensure};
+use bytesize::ByteSize;
 use eframe::egui;
-use eframe::egui::TextureHandle;
+use eframe::egui::{ColorImage, Context, FontDefinitions, TextureHandle, TextureOptions};
+use image::codecs::gif::GifDecoder;
+use image::codecs::jpeg::JpegEncoder;
+use image::codecs::png::PngDecoder;
+use image::codecs::webp::WebPDecoder;
+use image::imageops::FilterType;
+use image::{AnimationDecoder, DynamicImage, Frames, ImageFormat, Rgba, RgbaImage};
+use rayon::prelude::*;
+use sha2::{Digest, Sha256};
+use tracing::{info, warn};
+use walkdir::WalkDir;
+
+use crate::media::{format_duration, probe};
+
+/// Extensions (lowercase) of the image formats accepted for upload; used by every image picker
+pub const IMAGE_EXTENSIONS: [&str; 7] = ["png", "apng", "jpg", "jpeg", "gif", "webp", "bmp"];
+
+/// Extensions (lowercase) of the video formats for which contact sheets are generated
+pub const VIDEO_EXTENSIONS: [&str; 11] =
+    ["mp4", "m4v", "mkv", "avi", "mov", "wmv", "webm", "flv", "mpg", "mpeg", "ts"];
+
+// Longest side of a decoded preview; large enough for the hover preview at the default image area
+const THUMBNAIL_SIZE: u32 = 512;
+// Animations are truncated to bound memory usage
+const MAX_FRAMES: usize = 500;
+// Like browsers, treat (nearly) zero delays as the conventional default of 100 ms
+const MIN_FRAME_DELAY: Duration = Duration::from_millis(20);
+const DEFAULT_FRAME_DELAY: Duration = Duration::from_millis(100);
+
+// 4 columns of 480 px tiles keep contact sheets within the usual 2048 px limit of image hosts
+const CONTACT_SHEET_FRAMES: usize = 16;
+const CONTACT_SHEET_COLUMNS: usize = 4;
+const TILE_SIZE: u32 = 480;
+const SHEET_SPACING: u32 = 8;
+const SHEET_BACKGROUND: Rgba<u8> = Rgba([32, 32, 32, 255]);
+const HEADER_FONT_SIZE: f32 = 22.;
+const TIMESTAMP_FONT_SIZE: f32 = 16.;
+const JPEG_QUALITY: u8 = 90;
+// Twice as many frames as tiles are sampled so that rejected frames can be replaced
+const CANDIDATES_PER_FRAME: usize = 2;
+// A frame is black if nearly all of its pixels are darker than this luma, e.g. fades and intros
+const BLACK_LUMA: u8 = 32;
+const BLACK_RATIO: f32 = 0.98;
+// Images and frames whose difference hashes differ in fewer bits are near-duplicates
+const DUPLICATE_DISTANCE: u32 = 6;
 
 #[derive(Clone)]
 pub struct Image {
     pub path: PathBuf,
     pub filename: String,
     pub size: u64,
-    // TODO: Add GIF animation
-    // see https://github.com/Gui-Yom/vibin/blob/26e1a89a193d16754a1e33bd495aa51cd9b886a1/src/main.rs
+    // First (or only) frame
     pub texture_handle: Option<TextureHandle>,
+    // Every frame with its delay if the image is animated, otherwise empty
+    pub animation: Vec<(TextureHandle, Duration)>,
+    // Whether the image is still being decoded in the background
+    pub is_loading: bool,
+    // Path and size of the preprocessed copy, if any
+    pub processed: Option<(PathBuf, u64)>,
+    pub is_processing: bool,
+    // Computed along with the texture
+    pub hashes: Option<ImageHashes>,
+    // URL of the copy on the image host, if uploaded
+    pub hosted_url: Option<String>,
+    pub is_uploading: bool,
 }
 
 impl Image {
@@ -24,6 +88,32 @@ impl Image {
             .sqrt();
         texture_handle.size_vec2() * scaling_factor
     }
+
+    /// Return the path of the file to upload: the preprocessed copy if any, otherwise the original
+    pub fn upload_path(&self) -> &Path {
+        match &self.processed {
+            Some((path, _)) => path,
+            None => &self.path,
+        }
+    }
+
+    /// Return the frame to show now, scheduling a repaint for the next one if animated
+    pub fn current_texture(&self, ctx: &Context) -> Option<&TextureHandle> {
+        let total: Duration = self.animation.iter().map(|(_, delay)| *delay).sum();
+        if total.is_zero() {
+            return self.texture_handle.as_ref();
+        }
+
+        let mut elapsed = Duration::from_secs_f64(ctx.input(|i| i.time) % total.as_secs_f64());
+        for (texture_handle, delay) in self.animation.iter() {
+            if elapsed < *delay {
+                ctx.request_repaint_after(*delay - elapsed);
+                return Some(texture_handle);
+            }
+            elapsed -= *delay;
+        }
+        self.texture_handle.as_ref()
+    }
 }
 
 impl PartialEq for Image {
@@ -34,6 +124,644 @@ impl PartialEq for Image {
 
 impl Eq for Image {}
 
-pub fn generate_video_thumbnail_image<P: AsRef<Path>>(video_path: P) -> anyhow::Result<()> {
-    todo!()
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DuplicateKind {
+    // Byte for byte
+    Identical,
+    // e.g. resized or recompressed
+    Similar,
+}
+
+/// Hashes identifying the content of an image
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ImageHashes {
+    // SHA-256 of the file
+    pub content: [u8; 32],
+    // Difference hash of the decoded image
+    pub perceptual: u64,
+}
+
+impl ImageHashes {
+    fn new(path: &Path, image: &RgbaImage) -> anyhow::Result<Self> {
+        Ok(Self {
+            content: Sha256::digest(fs::read(path)?).into(),
+            perceptual: difference_hash(image),
+        })
+    }
+
+    pub fn compare(&self, other: &Self) -> Option<DuplicateKind> {
+        if self.content == other.content {
+            Some(DuplicateKind::Identical)
+        } else if (self.perceptual ^ other.perceptual).count_ones() < DUPLICATE_DISTANCE {
+            Some(DuplicateKind::Similar)
+        } else {
+            None
+        }
+    }
+}
+
+type DecodedImage = (
+    PathBuf,
+    anyhow::Result<(Vec<(ColorImage, Duration)>, Option<ImageHashes>)>,
+);
+
+/// Decodes and downscales images on the thread pool, caching the thumbnails on disk
+pub struct ImageLoader {
+    cache_dir: PathBuf,
+    channel: (mpsc::Sender<DecodedImage>, mpsc::Receiver<DecodedImage>),
+}
+
+impl ImageLoader {
+    pub fn new(cache_dir: PathBuf) -> Self {
+        Self {
+            cache_dir,
+            channel: mpsc::channel(),
+        }
+    }
+
+    /// Return a placeholder for the image at `path` and start decoding it in the background
+    pub fn load<P: AsRef<Path>>(&self, path: P, ctx: &Context) -> Image {
+        let path = path.as_ref().to_path_buf();
+        let image = Image {
+            filename: path
+                .file_name()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .into_owned(),
+            size: path.metadata().map(|m| m.len()).unwrap_or_default(),
+            texture_handle: None,
+            animation: Vec::new(),
+            is_loading: true,
+            processed: None,
+            is_processing: false,
+            hashes: None,
+            hosted_url: None,
+            is_uploading: false,
+            path,
+        };
+
+        let path = image.path.clone();
+        let cache_dir = self.cache_dir.clone();
+        let sender = self.channel.0.clone();
+        let ctx = ctx.clone();
+        rayon::spawn(move || {
+            let frames = match load_animation(&path) {
+                Ok(Some(frames)) => Ok(frames),
+                Ok(None) => load_thumbnail(&path, &cache_dir)
+                    .map(|thumbnail| vec![(thumbnail, Duration::ZERO)]),
+                Err(err) => Err(err),
+            }
+            .map(|frames| {
+                let first_frame = frames.first().map(|(frame, _)| frame);
+                let hashes = match first_frame.map(|frame| ImageHashes::new(&path, frame)) {
+                    Some(Ok(hashes)) => Some(hashes),
+                    Some(Err(err)) => {
+                        warn!(?err, "Unable to hash the image; duplicates cannot be detected");
+                        None
+                    }
+                    None => None,
+                };
+                let frames = frames
+                    .into_iter()
+                    .map(|(frame, delay)| {
+                        let colour_image = ColorImage::from_rgba_unmultiplied(
+                            [frame.width() as _, frame.height() as _],
+                            frame.as_flat_samples().as_slice(),
+                        );
+                        (colour_image, delay)
+                    })
+                    .collect();
+                (frames, hashes)
+            });
+            // The receiver only disappears when the application is closing
+            let _ = sender.send((path, frames));
+            ctx.request_repaint();
+        });
+        image
+    }
+
+    /// Upload the textures of the images decoded since the last call and return their paths;
+    /// images that have been removed in the meantime are skipped
+    pub fn poll(&self, images: &mut [Image], ctx: &Context) -> Vec<PathBuf> {
+        let mut loaded = Vec::new();
+        for (path, result) in self.channel.1.try_iter() {
+            let Some(image) = images.iter_mut().find(|image| image.path == path) else {
+                continue;
+            };
+            image.is_loading = false;
+            match result {
+                Ok((frames, hashes)) => {
+                    image.hashes = hashes;
+                    let is_animated = frames.len() > 1;
+                    let textures: Vec<(TextureHandle, Duration)> = frames
+                        .into_iter()
+                        .enumerate()
+                        .map(|(index, (colour_image, delay))| {
+                            let name = format!("{}#{index}", path.to_string_lossy());
+                            (ctx.load_texture(name, colour_image, TextureOptions::LINEAR), delay)
+                        })
+                        .collect();
+                    image.texture_handle = textures.first().map(|(texture, _)| texture.clone());
+                    if is_animated {
+                        image.animation = textures;
+                    }
+                    loaded.push(path);
+                }
+                Err(err) => warn!(
+                    ?err,
+                    "Unable to load image as texture; image preview unavailable"
+                ),
+            }
+        }
+        loaded
+    }
+}
+
+/// Return the cache path of the thumbnail of `path`, keyed by its path, modification time and size
+fn thumbnail_path(path: &Path, cache_dir: &Path) -> anyhow::Result<PathBuf> {
+    let metadata = path.metadata()?;
+    let modified = metadata.modified()?.duration_since(UNIX_EPOCH)?.as_nanos();
+    let key = Sha256::digest(format!(
+        "{}\0{modified}\0{}",
+        path.to_string_lossy(),
+        metadata.len()
+    ));
+    Ok(cache_dir.join(format!("{key:x}.png")))
+}
+
+fn downscale(image: RgbaImage) -> RgbaImage {
+    if image.width() > THUMBNAIL_SIZE || image.height() > THUMBNAIL_SIZE {
+        image::DynamicImage::ImageRgba8(image)
+            .resize(THUMBNAIL_SIZE, THUMBNAIL_SIZE, FilterType::Triangle)
+            .to_rgba8()
+    } else {
+        image
+    }
+}
+
+/// Decode every (downscaled) frame of an animated GIF, APNG or WebP with its delay, or return
+/// `None` if the image is not animated
+fn load_animation(path: &Path) -> anyhow::Result<Option<Vec<(RgbaImage, Duration)>>> {
+    let reader = || File::open(path).map(BufReader::new);
+    // Guessed from the content since APNG files commonly use the `.png` extension and vice versa
+    let format = image::io::Reader::open(path)?.with_guessed_format()?.format();
+    let frames: Frames = match format {
+        Some(ImageFormat::Gif) => GifDecoder::new(reader()?)?.into_frames(),
+        Some(ImageFormat::Png) => {
+            let decoder = PngDecoder::new(reader()?)?;
+            if !decoder.is_apng() {
+                return Ok(None);
+            }
+            decoder.apng().into_frames()
+        }
+        Some(ImageFormat::WebP) => {
+            let decoder = WebPDecoder::new(reader()?)?;
+            if !decoder.has_animation() {
+                return Ok(None);
+            }
+            decoder.into_frames()
+        }
+        _ => return Ok(None),
+    };
+
+    let frames = frames
+        .take(MAX_FRAMES)
+        .map(|frame| {
+            let frame = frame?;
+            let delay = Duration::from(frame.delay());
+            let delay = if delay < MIN_FRAME_DELAY {
+                DEFAULT_FRAME_DELAY
+            } else {
+                delay
+            };
+            Ok((downscale(frame.into_buffer()), delay))
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    Ok((frames.len() > 1).then_some(frames))
+}
+
+fn load_thumbnail(path: &Path, cache_dir: &Path) -> anyhow::Result<RgbaImage> {
+    let cache_path = thumbnail_path(path, cache_dir)?;
+    if let Ok(thumbnail) = image::open(&cache_path) {
+        return Ok(thumbnail.to_rgba8());
+    }
+
+    let image = image::io::Reader::open(path)?
+        .with_guessed_format()?
+        .decode()?;
+    let thumbnail = downscale(image.to_rgba8());
+    if let Err(err) = fs::create_dir_all(cache_dir)
+        .map_err(anyhow::Error::new)
+        .and_then(|()| thumbnail.save(&cache_path).map_err(anyhow::Error::new))
+    {
+        warn!(?err, "Unable to cache the thumbnail");
+    }
+    Ok(thumbnail)
+}
+
+/// Return whether `path` has the extension of an accepted image format, in any case
+pub fn is_supported_image<P: AsRef<Path>>(path: P) -> bool {
+    let extension = path.as_ref().extension().unwrap_or_default().to_string_lossy();
+    IMAGE_EXTENSIONS.contains(&extension.to_lowercase().as_str())
+}
+
+/// Return the 64-bit difference hash (dHash) of `image`, which barely changes when the image is
+/// resized or recompressed; similar images have hashes differing in few bits
+pub fn difference_hash(image: &RgbaImage) -> u64 {
+    let grey = image::imageops::grayscale(image);
+    let small = image::imageops::resize(&grey, 9, 8, FilterType::Triangle);
+    let mut hash = 0;
+    for y in 0..8 {
+        for x in 0..8 {
+            hash = hash << 1 | (small.get_pixel(x, y)[0] > small.get_pixel(x + 1, y)[0]) as u64;
+        }
+    }
+    hash
+}
+
+/// List the videos in the content, which may be a single file
+pub fn list_videos<P: AsRef<Path>>(content_path: P) -> Vec<PathBuf> {
+    WalkDir::new(content_path)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| {
+            entry.file_type().is_file()
+                && VIDEO_EXTENSIONS.contains(
+                    &entry
+                        .path()
+                        .extension()
+                        .unwrap_or_default()
+                        .to_string_lossy()
+                        .to_lowercase()
+                        .as_str(),
+                )
+        })
+        .map(|entry| entry.into_path())
+        .collect()
+}
+
+/// Run an ffmpeg command, failing clearly if ffmpeg is not installed
+fn run_ffmpeg(command: &mut Command) -> anyhow::Result<Output> {
+    command
+        .stdin(Stdio::null())
+        .output()
+        .map_err(|err| match err.kind() {
+            ErrorKind::NotFound => {
+                anyhow!("ffmpeg was not found on PATH; install it to generate contact sheets")
+            }
+            _ => anyhow::Error::new(err),
+        })
+}
+
+pub fn check_ffmpeg() -> anyhow::Result<()> {
+    run_ffmpeg(Command::new("ffmpeg").arg("-version")).map(|_| ())
+}
+
+/// Decode the frame at `time`, fitted within a tile
+fn extract_frame(video_path: &Path, time: Duration) -> anyhow::Result<RgbaImage> {
+    let output = run_ffmpeg(
+        Command::new("ffmpeg")
+            .args(["-v", "error", "-ss", &format!("{:.3}", time.as_secs_f64()), "-i"])
+            .arg(video_path)
+            .args([
+                "-frames:v",
+                "1",
+                "-vf",
+                &format!("scale={TILE_SIZE}:{TILE_SIZE}:force_original_aspect_ratio=decrease"),
+                "-f",
+                "image2pipe",
+                "-c:v",
+                "png",
+                "-",
+            ]),
+    )?;
+    ensure!(
+        output.status.success() && !output.stdout.is_empty(),
+        "Unable to extract the frame at {}: {}",
+        format_duration(time),
+        String::from_utf8_lossy(&output.stderr).trim()
+    );
+    Ok(image::load_from_memory_with_format(&output.stdout, ImageFormat::Png)?.to_rgba8())
+}
+
+fn is_black(frame: &RgbaImage) -> bool {
+    let grey = image::imageops::grayscale(frame);
+    let dark = grey.pixels().filter(|pixel| pixel[0] < BLACK_LUMA).count();
+    dark as f32 >= (grey.width() * grey.height()) as f32 * BLACK_RATIO
+}
+
+/// Drop black and near-duplicate frames, then pick `count` evenly spaced frames from the rest;
+/// the frames are only kept regardless if all of them would be dropped
+fn select_frames(
+    frames: Vec<(Duration, RgbaImage)>,
+    count: usize,
+) -> Vec<(Duration, RgbaImage)> {
+    let mut hashes: Vec<u64> = Vec::new();
+    let (mut kept, mut dropped) = (Vec::new(), Vec::new());
+    for (time, frame) in frames {
+        let hash = difference_hash(&frame);
+        if is_black(&frame)
+            || hashes
+                .iter()
+                .any(|other| (hash ^ other).count_ones() < DUPLICATE_DISTANCE)
+        {
+            dropped.push((time, frame));
+        } else {
+            hashes.push(hash);
+            kept.push((time, frame));
+        }
+    }
+
+    let frames = if kept.is_empty() { dropped } else { kept };
+    let length = frames.len();
+    if length <= count {
+        return frames;
+    }
+    let indices: Vec<usize> = (0..count).map(|index| index * length / count).collect();
+    frames
+        .into_iter()
+        .enumerate()
+        .filter(|(index, _)| indices.contains(index))
+        .map(|(_, frame)| frame)
+        .collect()
+}
+
+/// Draw a line of text with its top left corner at `position`
+fn draw_text(
+    canvas: &mut RgbaImage,
+    font: &FontVec,
+    text: &str,
+    position: (f32, f32),
+    size: f32,
+    colour: [u8; 3],
+) {
+    let font = font.as_scaled(PxScale::from(size));
+    let baseline = position.1 + font.ascent();
+    let mut x = position.0;
+    let mut previous = None;
+    for c in text.chars() {
+        let glyph_id = font.glyph_id(c);
+        if let Some(previous) = previous {
+            x += font.kern(previous, glyph_id);
+        }
+        previous = Some(glyph_id);
+        let glyph = glyph_id.with_scale_and_position(font.scale(), point(x, baseline));
+        x += font.h_advance(glyph_id);
+
+        let Some(outline) = font.outline_glyph(glyph) else {
+            continue;
+        };
+        let bounds = outline.px_bounds();
+        outline.draw(|glyph_x, glyph_y, coverage| {
+            let x = bounds.min.x as i64 + glyph_x as i64;
+            let y = bounds.min.y as i64 + glyph_y as i64;
+            if x < 0 || y < 0 || x >= canvas.width() as i64 || y >= canvas.height() as i64 {
+                return;
+            }
+            let pixel = canvas.get_pixel_mut(x as u32, y as u32);
+            for (channel, colour) in pixel.0.iter_mut().zip(colour) {
+                *channel = (*channel as f32 * (1. - coverage) + colour as f32 * coverage) as u8;
+            }
+        });
+    }
+}
+
+/// Lay the frames out in a grid below the header lines, with the timestamp on each frame
+fn compose_contact_sheet(
+    header: &[String],
+    frames: &[(Duration, RgbaImage)],
+) -> anyhow::Result<RgbaImage> {
+    // The monospace font bundled with egui
+    let font_data = FontDefinitions::default()
+        .font_data
+        .remove("Hack")
+        .ok_or_else(|| anyhow!("The bundled font is missing"))?;
+    let font = FontVec::try_from_vec_and_index(font_data.font.into_owned(), font_data.index)?;
+
+    let columns = CONTACT_SHEET_COLUMNS.min(frames.len()).max(1) as u32;
+    let rows = (frames.len() as u32).div_ceil(columns);
+    let tile_height = frames
+        .iter()
+        .map(|(_, frame)| frame.height())
+        .max()
+        .unwrap_or_default();
+    let line_height = (HEADER_FONT_SIZE * 1.4) as u32;
+    let header_height = SHEET_SPACING + header.len() as u32 * line_height;
+    let mut sheet = RgbaImage::from_pixel(
+        SHEET_SPACING + columns * (TILE_SIZE + SHEET_SPACING),
+        header_height + SHEET_SPACING + rows * (tile_height + SHEET_SPACING),
+        SHEET_BACKGROUND,
+    );
+
+    for (index, line) in header.iter().enumerate() {
+        let y = SHEET_SPACING + index as u32 * line_height;
+        draw_text(
+            &mut sheet,
+            &font,
+            line,
+            (SHEET_SPACING as f32, y as f32),
+            HEADER_FONT_SIZE,
+            [255; 3],
+        );
+    }
+    for (index, (time, frame)) in frames.iter().enumerate() {
+        let (column, row) = (index as u32 % columns, index as u32 / columns);
+        // Centred within the tile since portrait frames are narrower
+        let x = SHEET_SPACING
+            + column * (TILE_SIZE + SHEET_SPACING)
+            + (TILE_SIZE - frame.width()) / 2;
+        let y = header_height + SHEET_SPACING + row * (tile_height + SHEET_SPACING);
+        image::imageops::overlay(&mut sheet, frame, x as i64, y as i64);
+
+        let label = format_duration(*time);
+        let position = (
+            x as f32 + 6.,
+            (y + frame.height()) as f32 - TIMESTAMP_FONT_SIZE * 1.2 - 4.,
+        );
+        // Shadowed to stay legible on bright frames
+        let shadow = (position.0 + 1., position.1 + 1.);
+        draw_text(&mut sheet, &font, &label, shadow, TIMESTAMP_FONT_SIZE, [0; 3]);
+        draw_text(&mut sheet, &font, &label, position, TIMESTAMP_FONT_SIZE, [255; 3]);
+    }
+    Ok(sheet)
+}
+
+/// Return the path of the contact sheet of the video in `output_dir`, suffixed with a hash of the
+/// video's path so that videos with the same name do not collide
+fn contact_sheet_path(video_path: &Path, output_dir: &Path) -> PathBuf {
+    let stem = video_path.file_stem().unwrap_or_default().to_string_lossy();
+    let key = format!("{:x}", Sha256::digest(video_path.to_string_lossy().as_bytes()));
+    output_dir.join(format!("{stem}-{} contact sheet.jpg", &key[..8]))
+}
+
+/// Generate a contact sheet of evenly spaced frames of the video with ffmpeg, skipping black and
+/// near-duplicate frames, and return the path of the JPEG saved in `output_dir`
+pub fn generate_video_thumbnail_image<P: AsRef<Path>, Q: AsRef<Path>>(
+    video_path: P,
+    output_dir: Q,
+) -> anyhow::Result<PathBuf> {
+    let video_path = video_path.as_ref();
+    let info = probe(video_path)?;
+    let (Some(duration), Some(resolution)) = (info.duration, info.resolution()) else {
+        anyhow::bail!("Unable to read the duration and resolution of the video");
+    };
+
+    let candidates = CONTACT_SHEET_FRAMES * CANDIDATES_PER_FRAME;
+    let frames: Vec<(Duration, RgbaImage)> = (0..candidates)
+        .into_par_iter()
+        .map(|index| duration.mul_f64((index as f64 + 0.5) / candidates as f64))
+        .filter_map(|time| match extract_frame(video_path, time) {
+            Ok(frame) => Some((time, frame)),
+            Err(err) => {
+                warn!(?err, "Unable to extract a frame of {}", video_path.to_string_lossy());
+                None
+            }
+        })
+        .collect();
+    ensure!(!frames.is_empty(), "Unable to extract any frame of the video");
+
+    let filename = video_path
+        .file_name()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .into_owned();
+    let size = video_path.metadata().map(|m| m.len()).unwrap_or_default();
+    let header = [
+        filename.clone(),
+        format!(
+            "Duration: {} | Resolution: {resolution} | Size: {}",
+            format_duration(duration),
+            ByteSize(size)
+        ),
+    ];
+    let sheet = compose_contact_sheet(&header, &select_frames(frames, CONTACT_SHEET_FRAMES))?;
+
+    fs::create_dir_all(output_dir.as_ref())?;
+    let path = contact_sheet_path(video_path, output_dir.as_ref());
+    JpegEncoder::new_with_quality(BufWriter::new(File::create(&path)?), JPEG_QUALITY)
+        .encode_image(&DynamicImage::ImageRgba8(sheet).to_rgb8())?;
+    info!("Generated the contact sheet of {filename}");
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_contact_sheet_path() {
+        let output_dir = Path::new("/tmp/qtm2/images");
+        let first = contact_sheet_path(Path::new("/videos/a/clip.mp4"), output_dir);
+        let second = contact_sheet_path(Path::new("/videos/b/clip.mp4"), output_dir);
+        assert_ne!(first, second);
+        assert_eq!(first, contact_sheet_path(Path::new("/videos/a/clip.mp4"), output_dir));
+        assert!(first.file_name().unwrap().to_string_lossy().starts_with("clip-"));
+    }
+
+    #[test]
+    fn test_load_thumbnail() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache_dir = dir.path().join("thumbnails");
+        let path = dir.path().join("wide.png");
+        RgbaImage::new(1024, 16).save(&path).unwrap();
+
+        let thumbnail = load_thumbnail(&path, &cache_dir).unwrap();
+        assert_eq!(thumbnail.dimensions(), (512, 8));
+        assert!(thumbnail_path(&path, &cache_dir).unwrap().is_file());
+
+        // Modifying the image invalidates the cached thumbnail
+        RgbaImage::new(100, 50).save(&path).unwrap();
+        assert_eq!(load_thumbnail(&path, &cache_dir).unwrap().dimensions(), (100, 50));
+    }
+
+    #[test]
+    fn test_load_animation() {
+        use image::codecs::gif::GifEncoder;
+        use image::{Delay, Frame, Rgba};
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("animated.gif");
+        let frames = [(Rgba([255, 0, 0, 255]), 50), (Rgba([0, 0, 255, 255]), 0)]
+            .into_iter()
+            .map(|(colour, delay)| {
+                Frame::from_parts(
+                    RgbaImage::from_pixel(8, 8, colour),
+                    0,
+                    0,
+                    Delay::from_numer_denom_ms(delay, 1),
+                )
+            });
+        GifEncoder::new(File::create(&path).unwrap())
+            .encode_frames(frames)
+            .unwrap();
+
+        let animation = load_animation(&path).unwrap().unwrap();
+        assert_eq!(animation.len(), 2);
+        assert_eq!(animation[0].1, Duration::from_millis(50));
+        assert_eq!(animation[1].1, DEFAULT_FRAME_DELAY);
+        assert_eq!(animation[1].0.get_pixel(0, 0), &Rgba([0, 0, 255, 255]));
+
+        let still = dir.path().join("still.png");
+        RgbaImage::new(8, 8).save(&still).unwrap();
+        assert_eq!(load_animation(&still).unwrap(), None);
+    }
+
+    #[test]
+    fn test_select_frames() {
+        // A gradient, its mirror image and stripes have very different hashes
+        let gradient = RgbaImage::from_fn(32, 32, |x, _| Rgba([x as u8 * 8, 128, 128, 255]));
+        let mirrored = image::imageops::flip_horizontal(&gradient);
+        let stripes =
+            RgbaImage::from_fn(32, 32, |x, _| Rgba([(x / 4 % 2) as u8 * 255, 64, 64, 255]));
+        let black = RgbaImage::from_pixel(32, 32, Rgba([0, 0, 0, 255]));
+        let frame = |seconds, image: &RgbaImage| (Duration::from_secs(seconds), image.clone());
+
+        let frames = vec![
+            frame(0, &black),
+            frame(1, &gradient),
+            frame(2, &gradient),
+            frame(3, &mirrored),
+            frame(4, &black),
+            frame(5, &stripes),
+        ];
+        let times = |frames: Vec<(Duration, RgbaImage)>| -> Vec<u64> {
+            frames.into_iter().map(|(time, _)| time.as_secs()).collect()
+        };
+        assert_eq!(times(select_frames(frames.clone(), 16)), [1, 3, 5]);
+        assert_eq!(times(select_frames(frames, 2)), [1, 3]);
+        assert_eq!(
+            times(select_frames(vec![frame(0, &black), frame(1, &black)], 16)),
+            [0, 1]
+        );
+
+        let sheet = compose_contact_sheet(
+            &["video.mp4".to_owned()],
+            &select_frames(vec![frame(1, &gradient), frame(3, &mirrored)], 16),
+        )
+        .unwrap();
+        assert_eq!(sheet.width(), SHEET_SPACING + 2 * (TILE_SIZE + SHEET_SPACING));
+    }
+
+    #[test]
+    fn test_image_hashes() {
+        let dir = tempfile::tempdir().unwrap();
+        let dir = dir.path();
+        let gradient = RgbaImage::from_fn(64, 64, |x, y| Rgba([x as u8 * 4, y as u8 * 4, 0, 255]));
+        let copy = DynamicImage::ImageRgba8(gradient.clone()).resize(32, 32, FilterType::Triangle);
+        let mirrored = image::imageops::flip_horizontal(&gradient);
+        gradient.save(dir.join("original.png")).unwrap();
+        fs::copy(dir.join("original.png"), dir.join("copy.png")).unwrap();
+        copy.to_rgb8().save(dir.join("resized.jpg")).unwrap();
+        mirrored.save(dir.join("other.png")).unwrap();
+
+        let hashes = |filename: &str| {
+            let path = dir.join(filename);
+            ImageHashes::new(&path, &image::open(&path).unwrap().to_rgba8()).unwrap()
+        };
+        let original = hashes("original.png");
+        assert_eq!(original.compare(&hashes("copy.png")), Some(DuplicateKind::Identical));
+        assert_eq!(original.compare(&hashes("resized.jpg")), Some(DuplicateKind::Similar));
+        assert_eq!(original.compare(&hashes("other.png")), None);
+    }
 }
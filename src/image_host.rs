@@ -0,0 +1,216 @@
+// SPDX-License-Identifier: BSD-2-Clause-Patent
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{mpsc, Arc};
+use std::thread;
+use std::time::Duration;
+
+use anyhow::{anyhow, ensure};
+use eframe::egui::Context;
+use reqwest::blocking::multipart::{Form, Part};
+use reqwest::blocking::Client;
+use serde_json::Value;
+use tracing::{info, warn};
+
+use crate::image::Image;
+use crate::qtm_config::ImageHostConfig;
+
+const MAX_ATTEMPTS: u32 = 3;
+// Multiplied by the number of failed attempts
+const RETRY_DELAY: Duration = Duration::from_secs(1);
+const TIMEOUT: Duration = Duration::from_secs(60);
+
+/// A service hosting images at public URLs
+pub trait ImageHost: Send + Sync {
+    /// Upload the image at `path` and return its URL
+    fn upload(&self, path: &Path) -> anyhow::Result<String>;
+}
+
+/// An image host which takes the image as a multipart form and replies with JSON containing the
+/// URL, which is the API of most image hosts
+pub struct MultipartHost {
+    client: Client,
+    config: ImageHostConfig,
+}
+
+impl MultipartHost {
+    pub fn new(config: ImageHostConfig) -> anyhow::Result<Self> {
+        ensure!(!config.endpoint.is_empty(), "No image host is configured");
+        Ok(Self {
+            client: Client::builder().timeout(TIMEOUT).build()?,
+            config,
+        })
+    }
+}
+
+impl ImageHost for MultipartHost {
+    fn upload(&self, path: &Path) -> anyhow::Result<String> {
+        let filename = path
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .into_owned();
+        // Read upfront so that the request has a length rather than being chunked, which some
+        // hosts reject
+        let part = Part::bytes(fs::read(path)?).file_name(filename);
+        let mut request = self
+            .client
+            .post(&self.config.endpoint)
+            .multipart(Form::new().part(self.config.field_name.clone(), part));
+        if let Some((name, value)) = self.config.auth_header.split_once(':') {
+            request = request.header(name.trim(), value.trim());
+        }
+
+        let response = request.send()?.error_for_status()?;
+        let json: Value = serde_json::from_str(&response.text()?)?;
+        json_path(&json, &self.config.url_path)
+            .ok_or_else(|| anyhow!("No URL at `{}` in the response: {json}", self.config.url_path))
+    }
+}
+
+/// Return the string at a dot-separated path of object keys and array indices, e.g.
+/// "data.files.0.url"
+fn json_path(json: &Value, path: &str) -> Option<String> {
+    path.split('.')
+        .filter(|key| !key.is_empty())
+        .try_fold(json, |value, key| match (value, key.parse::<usize>()) {
+            (Value::Array(array), Ok(index)) => array.get(index),
+            _ => value.get(key),
+        })?
+        .as_str()
+        .map(str::to_owned)
+}
+
+pub fn upload_with_retry(host: &dyn ImageHost, path: &Path) -> anyhow::Result<String> {
+    let mut attempt = 1;
+    loop {
+        match host.upload(path) {
+            Ok(url) => return Ok(url),
+            Err(err) if attempt < MAX_ATTEMPTS => {
+                warn!(?err, attempt, "Unable to upload {}; retrying", path.to_string_lossy());
+                thread::sleep(RETRY_DELAY * attempt);
+                attempt += 1;
+            }
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+type UploadedImage = (PathBuf, anyhow::Result<String>);
+
+/// Uploads images to an image host one after another in the background
+pub struct ImageUploader {
+    channel: (mpsc::Sender<UploadedImage>, mpsc::Receiver<UploadedImage>),
+}
+
+impl Default for ImageUploader {
+    fn default() -> Self {
+        Self {
+            channel: mpsc::channel(),
+        }
+    }
+}
+
+impl ImageUploader {
+    /// Upload the preprocessed copies of the images, or the originals if not preprocessed;
+    /// images which are already hosted are skipped unless `is_rehosting`
+    pub fn upload(
+        &self,
+        images: &mut [Image],
+        host: Arc<dyn ImageHost>,
+        is_rehosting: bool,
+        ctx: &Context,
+    ) {
+        let paths: Vec<(PathBuf, PathBuf)> = images
+            .iter_mut()
+            .filter(|image| !image.is_uploading && (is_rehosting || image.hosted_url.is_none()))
+            .map(|image| {
+                image.hosted_url = None;
+                image.is_uploading = true;
+                (image.path.clone(), image.upload_path().to_path_buf())
+            })
+            .collect();
+
+        let sender = self.channel.0.clone();
+        let ctx = ctx.clone();
+        thread::spawn(move || {
+            for (path, upload_path) in paths {
+                let result = upload_with_retry(host.as_ref(), &upload_path);
+                // The receiver only disappears when the application is closing
+                let _ = sender.send((path, result));
+                ctx.request_repaint();
+            }
+        });
+    }
+
+    /// Return the error messages of the images that failed to be uploaded since the last call
+    pub fn poll(&self, images: &mut [Image]) -> Vec<String> {
+        let mut errors = Vec::new();
+        for (path, result) in self.channel.1.try_iter() {
+            let Some(image) = images.iter_mut().find(|image| image.path == path) else {
+                continue;
+            };
+            image.is_uploading = false;
+            match result {
+                Ok(url) => {
+                    info!("Uploaded {} to {url}", image.filename);
+                    image.hosted_url = Some(url);
+                }
+                Err(err) => {
+                    warn!(?err, "Unable to upload {}", path.to_string_lossy());
+                    errors.push(format!("{}: {err}", image.filename));
+                }
+            }
+        }
+        errors
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_util::serve;
+
+    use super::*;
+
+    #[test]
+    fn test_upload() {
+        let responses = [
+            (503, r#"{"error": "busy"}"#),
+            (200, r#"{"image": {"urls": ["https://images.example/a.png"]}}"#),
+            (200, r#"{"status": "ok"}"#),
+        ];
+        let (url, server) = serve(responses.len(), move |index, _| {
+            let (status, body) = responses[index];
+            (
+                status,
+                "Content-Type: application/json\r\n".to_owned(),
+                body.to_owned(),
+            )
+        });
+        let host = MultipartHost::new(ImageHostConfig {
+            endpoint: format!("{url}/upload"),
+            field_name: "source".to_owned(),
+            url_path: "image.urls.0".to_owned(),
+            auth_header: "Authorization: Client-ID secret".to_owned(),
+        })
+        .unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("image.png");
+        fs::write(&path, b"not really a PNG").unwrap();
+
+        assert_eq!(
+            upload_with_retry(&host, &path).unwrap(),
+            "https://images.example/a.png"
+        );
+        assert!(host.upload(&path).is_err());
+
+        let requests = server.join().unwrap();
+        assert_eq!(requests.len(), 3);
+        let request = requests[1].to_lowercase();
+        assert!(request.starts_with("post /upload"));
+        assert!(request.contains("authorization: client-id secret"));
+        assert!(request.contains(r#"name="source"; filename="image.png""#));
+        assert!(request.contains("not really a png"));
+    }
+}
@@ -16,6 +16,7 @@ use eframe::egui::{vec2, Color32, FontData, FontDefinitions, FontId, Pos2, Style
 use eframe::egui::{FontFamily, Margin, Rounding};
 use tracing::{error, info, Level};
 
+use crate::category::SiteCategories;
 use crate::image::Image;
 use crate::password_prompt::PasswordPrompt;
 use crate::qtm::Qtm;
@@ -24,16 +25,31 @@ use crate::qtm_networking::QtmNetworking;
 use crate::tag::TagData;
 
 mod category;
+mod clipboard;
+mod draft;
 mod file_dialog;
+mod history;
 mod image;
+mod image_host;
+mod media;
 mod password_prompt;
+mod preprocess;
+mod preview;
+mod privacy;
 mod qtm;
 mod qtm_config;
 mod qtm_networking;
 mod selectable_table;
+mod suggestion;
 mod tag;
+mod template;
+#[cfg(test)]
+mod test_util;
 mod torrent;
 mod unwrap_trace;
+mod upload_queue;
+mod validation;
+mod viewer;
 
 fn proj_dirs() -> Result<ProjectDirs> {
     ProjectDirs::from("", "", "qtm2").ok_or(anyhow::Error::from(Error::new(
@@ -77,6 +93,18 @@ fn initialise_dirs() -> Result<()> {
             return Err(anyhow::Error::new(err));
         }
     }
+    if !cache_dir("thumbnails").exists() {
+        if let Err(err) = fs::create_dir_all(cache_dir("thumbnails")) {
+            error!(?err, "Unable to create thumbnails folder; exiting");
+            return Err(anyhow::Error::new(err));
+        }
+    }
+    if !cache_dir("drafts").exists() {
+        if let Err(err) = fs::create_dir_all(cache_dir("drafts")) {
+            error!(?err, "Unable to create drafts folder; exiting");
+            return Err(anyhow::Error::new(err));
+        }
+    }
     Ok(())
 }
 
@@ -154,6 +182,7 @@ struct DialogMessage(Cow<'static, str>, bool);
 //          Add CLI support
 //          Add networking/communication/authentication features
 //          Add uTorrent/qBittorrent integration
+//          Submit queued uploads to the site and seed them (see upload_queue.rs)
 
 fn main() -> Result<()> {
     // Initialise directories
@@ -178,9 +207,6 @@ fn main() -> Result<()> {
         width: 512,
         height: 512,
     });
-    // Tags init
-    TagData::_init_data(cache_dir("tags.json"));
-    let tags = TagData::fetch_data(cache_dir("tags.json"));
 
     let is_authenticated = Rc::new(Cell::new(false));
     let is_authenticated_clone = is_authenticated.clone();
@@ -190,6 +216,13 @@ fn main() -> Result<()> {
     let networking_clone = networking.clone();
     info!("Started networking");
 
+    // Tags init
+    if !cache_dir("tags.json").exists() {
+        TagData::init_data(cache_dir("tags.json"));
+    }
+    // Synced in the background once the upload page is shown
+    let tags = TagData::fetch_data(cache_dir("tags.json"));
+
     #[cfg(not(debug_assertions))]
     {
         // Egui init
@@ -228,6 +261,10 @@ fn main() -> Result<()> {
         }
     }
 
+    // Categories init; synced in the background once the upload page is shown
+    let site_categories = SiteCategories::load_cached(cache_dir("categories.json"));
+    let client = networking.client.clone();
+
     eframe::run_native(
         &format!(
             "Quick Torrent Maker 2 v{}",
@@ -241,7 +278,7 @@ fn main() -> Result<()> {
             icon_data,
             ..Default::default()
         },
-        Box::new(|cc| Box::new(Qtm::new(cc, config, tags))),
+        Box::new(|cc| Box::new(Qtm::new(cc, config, tags, site_categories, client))),
     )
     .map_err(|err| {
         error!(?err, "QTM2 failed to set up a graphics context");
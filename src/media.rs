@@ -0,0 +1,278 @@
+// SPDX-License-Identifier: BSD-2-Clause-Patent
+
+use std::collections::BTreeMap;
+use std::io::ErrorKind;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::time::Duration;
+
+use anyhow::{anyhow, ensure};
+use serde::{Deserialize, Serialize};
+
+/// Technical details of a video file, as reported by ffprobe
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct MediaInfo {
+    pub duration: Option<Duration>,
+    pub container: String,
+    // Bits per second
+    pub bitrate: Option<u64>,
+    // First video stream; cover art is not counted
+    pub video: Option<VideoStream>,
+    pub audio: Vec<AudioStream>,
+    pub subtitles: Vec<SubtitleStream>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct VideoStream {
+    pub codec: String,
+    // As displayed, i.e. swapped if the video is rotated by 90°
+    pub width: u32,
+    pub height: u32,
+    pub frame_rate: Option<f64>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct AudioStream {
+    pub codec: String,
+    // e.g. "stereo" or "5.1(side)", or the number of channels if the layout is unknown
+    pub channels: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct SubtitleStream {
+    pub codec: String,
+    pub language: Option<String>,
+}
+
+// The subset of `ffprobe -print_format json -show_format -show_streams` in use; ffprobe writes
+// most numbers as strings
+#[derive(Deserialize)]
+struct ProbeOutput {
+    format: ProbeFormat,
+    #[serde(default)]
+    streams: Vec<ProbeStream>,
+}
+
+#[derive(Deserialize)]
+struct ProbeFormat {
+    #[serde(default)]
+    format_long_name: String,
+    duration: Option<String>,
+    bit_rate: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ProbeStream {
+    #[serde(default)]
+    codec_type: String,
+    #[serde(default)]
+    codec_name: String,
+    width: Option<u32>,
+    height: Option<u32>,
+    avg_frame_rate: Option<String>,
+    channels: Option<u32>,
+    channel_layout: Option<String>,
+    #[serde(default)]
+    disposition: BTreeMap<String, u8>,
+    #[serde(default)]
+    tags: BTreeMap<String, String>,
+    #[serde(default)]
+    side_data_list: Vec<BTreeMap<String, serde_json::Value>>,
+}
+
+impl ProbeStream {
+    fn rotation(&self) -> i64 {
+        self.side_data_list
+            .iter()
+            .find_map(|side_data| side_data.get("rotation")?.as_i64())
+            .or_else(|| self.tags.get("rotate")?.parse().ok())
+            .unwrap_or_default()
+    }
+}
+
+/// Parse a frame rate written as a fraction, e.g. "30000/1001"; "0/0" means unknown
+fn parse_frame_rate(frame_rate: &str) -> Option<f64> {
+    let (numerator, denominator) = frame_rate.split_once('/')?;
+    let numerator = numerator.parse::<f64>().ok()?;
+    let denominator = denominator.parse::<f64>().ok()?;
+    (numerator > 0. && denominator > 0.).then(|| numerator / denominator)
+}
+
+/// Format `duration` as `HH:MM:SS`
+pub fn format_duration(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+    format!("{:02}:{:02}:{:02}", seconds / 3600, seconds / 60 % 60, seconds % 60)
+}
+
+pub fn format_bitrate(bitrate: u64) -> String {
+    if bitrate >= 1_000_000 {
+        format!("{:.1} Mbit/s", bitrate as f64 / 1_000_000.)
+    } else {
+        format!("{} kbit/s", bitrate / 1000)
+    }
+}
+
+impl MediaInfo {
+    fn parse(json: &str) -> anyhow::Result<Self> {
+        let output: ProbeOutput = serde_json::from_str(json)?;
+        let mut info = Self {
+            duration: output
+                .format
+                .duration
+                .and_then(|duration| duration.parse::<f64>().ok())
+                .filter(|seconds| seconds.is_finite() && *seconds >= 0.)
+                .map(Duration::from_secs_f64),
+            container: output.format.format_long_name,
+            bitrate: output.format.bit_rate.and_then(|bitrate| bitrate.parse().ok()),
+            ..Default::default()
+        };
+
+        for stream in output.streams {
+            let language = stream.tags.get("language").filter(|language| *language != "und");
+            match stream.codec_type.as_str() {
+                "video" if info.video.is_none()
+                    && stream.disposition.get("attached_pic") != Some(&1) =>
+                {
+                    let (mut width, mut height) =
+                        (stream.width.unwrap_or_default(), stream.height.unwrap_or_default());
+                    if stream.rotation() % 180 != 0 {
+                        (width, height) = (height, width);
+                    }
+                    info.video = Some(VideoStream {
+                        codec: stream.codec_name.to_uppercase(),
+                        width,
+                        height,
+                        frame_rate: stream.avg_frame_rate.as_deref().and_then(parse_frame_rate),
+                    });
+                }
+                "audio" => info.audio.push(AudioStream {
+                    codec: stream.codec_name.to_uppercase(),
+                    channels: stream
+                        .channel_layout
+                        .or_else(|| stream.channels.map(|channels| format!("{channels} ch")))
+                        .unwrap_or_default(),
+                }),
+                "subtitle" => info.subtitles.push(SubtitleStream {
+                    codec: stream.codec_name,
+                    language: language.cloned(),
+                }),
+                _ => {}
+            }
+        }
+        Ok(info)
+    }
+
+    pub fn resolution(&self) -> Option<String> {
+        self.video
+            .as_ref()
+            .map(|video| format!("{}x{}", video.width, video.height))
+    }
+
+    /// e.g. "H264 1920x1080 29.97 fps"
+    pub fn video_description(&self) -> Option<String> {
+        let video = self.video.as_ref()?;
+        let mut description = format!("{} {}x{}", video.codec, video.width, video.height);
+        if let Some(frame_rate) = video.frame_rate {
+            // Trailing zeros are dropped, e.g. 25 rather than 25.00
+            let frame_rate = format!("{frame_rate:.2}");
+            let frame_rate = frame_rate.trim_end_matches('0').trim_end_matches('.');
+            description.push_str(&format!(" {frame_rate} fps"));
+        }
+        Some(description)
+    }
+
+    /// e.g. "AAC stereo, AC3 5.1(side)"
+    pub fn audio_description(&self) -> Option<String> {
+        (!self.audio.is_empty()).then(|| {
+            self.audio
+                .iter()
+                .map(|audio| format!("{} {}", audio.codec, audio.channels).trim().to_owned())
+                .collect::<Vec<String>>()
+                .join(", ")
+        })
+    }
+
+    /// The languages, or codecs if unknown, of the subtitle tracks, e.g. "eng, fre, subrip"
+    pub fn subtitle_description(&self) -> Option<String> {
+        (!self.subtitles.is_empty()).then(|| {
+            self.subtitles
+                .iter()
+                .map(|subtitle| subtitle.language.as_ref().unwrap_or(&subtitle.codec).as_str())
+                .collect::<Vec<&str>>()
+                .join(", ")
+        })
+    }
+
+    /// Every known detail on one line
+    pub fn summary(&self) -> String {
+        [
+            self.duration.map(format_duration),
+            (!self.container.is_empty()).then(|| self.container.clone()),
+            self.video_description(),
+            self.bitrate.map(format_bitrate),
+            self.audio_description(),
+            self.subtitle_description()
+                .map(|subtitles| format!("Subtitles: {subtitles}")),
+        ]
+        .into_iter()
+        .flatten()
+        .collect::<Vec<String>>()
+        .join(" · ")
+    }
+}
+
+/// Probe the video at `path` with the ffprobe binary on PATH
+pub fn probe<P: AsRef<Path>>(path: P) -> anyhow::Result<MediaInfo> {
+    let output = Command::new("ffprobe")
+        .args(["-v", "error", "-print_format", "json", "-show_format", "-show_streams"])
+        .arg(path.as_ref())
+        .stdin(Stdio::null())
+        .output()
+        .map_err(|err| match err.kind() {
+            ErrorKind::NotFound => anyhow!("ffprobe was not found on PATH; install ffmpeg"),
+            _ => anyhow::Error::new(err),
+        })?;
+    ensure!(
+        output.status.success(),
+        "ffprobe failed: {}",
+        String::from_utf8_lossy(&output.stderr).trim()
+    );
+    MediaInfo::parse(&String::from_utf8_lossy(&output.stdout))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let json = r#"{
+            "streams": [
+                {"codec_name": "mjpeg", "codec_type": "video", "width": 600, "height": 600,
+                 "disposition": {"default": 0, "attached_pic": 1}},
+                {"codec_name": "h264", "codec_type": "video", "width": 1920, "height": 1080,
+                 "avg_frame_rate": "30000/1001", "disposition": {"attached_pic": 0},
+                 "side_data_list": [{"side_data_type": "Display Matrix", "rotation": -90}]},
+                {"codec_name": "aac", "codec_type": "audio", "channels": 2,
+                 "channel_layout": "stereo", "tags": {"language": "eng"}},
+                {"codec_name": "ac3", "codec_type": "audio", "channels": 6},
+                {"codec_name": "subrip", "codec_type": "subtitle", "tags": {"language": "fre"}},
+                {"codec_name": "ass", "codec_type": "subtitle", "tags": {"language": "und"}}
+            ],
+            "format": {"format_long_name": "QuickTime / MOV", "duration": "3723.500000",
+                       "bit_rate": "5000000"}
+        }"#;
+        let info = MediaInfo::parse(json).unwrap();
+        assert_eq!(info.duration, Some(Duration::from_secs_f64(3723.5)));
+        assert_eq!(info.resolution().as_deref(), Some("1080x1920"));
+        assert_eq!(
+            info.summary(),
+            "01:02:03 · QuickTime / MOV · H264 1080x1920 29.97 fps · 5.0 Mbit/s · \
+             AAC stereo, AC3 6 ch · Subtitles: fre, ass"
+        );
+
+        let info = MediaInfo::parse(r#"{"format": {"duration": "N/A"}}"#).unwrap();
+        assert_eq!(info, MediaInfo::default());
+        assert_eq!(info.summary(), "");
+    }
+}
@@ -0,0 +1,493 @@
+// SPDX-License-Identifier: BSD-2-Clause-Patent
+
+use std::fs;
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+
+use anyhow::{anyhow, ensure};
+use bytesize::ByteSize;
+use eframe::egui::Context;
+use image::codecs::gif::GifDecoder;
+use image::codecs::jpeg::JpegEncoder;
+use image::codecs::png::PngDecoder;
+use image::codecs::webp::WebPDecoder;
+use image::imageops::FilterType;
+use image::{AnimationDecoder, DynamicImage, ImageFormat};
+use sha2::{Digest, Sha256};
+use tracing::warn;
+
+use crate::image::Image;
+use crate::qtm_config::SiteProfile;
+
+// JPEG qualities tried in turn until the image fits within the maximum file size
+const JPEG_QUALITIES: [u8; 5] = [90, 80, 70, 60, 50];
+// Factor by which the dimensions shrink when even the lowest quality is too large
+const DOWNSCALE_FACTOR: f32 = 0.75;
+const MAX_DOWNSCALES: usize = 8;
+
+// JPEG segments carrying metadata: APP1 (EXIF/XMP), APP2 (ICC), APP13 (IPTC) and comments
+const JPEG_METADATA_MARKERS: [u8; 4] = [0xE1, 0xE2, 0xED, 0xFE];
+const PNG_SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1A, b'\n'];
+const PNG_METADATA_CHUNKS: [&[u8; 4]; 6] =
+    [b"eXIf", b"iTXt", b"tEXt", b"zTXt", b"iCCP", b"tIME"];
+const WEBP_METADATA_CHUNKS: [&[u8; 4]; 3] = [b"EXIF", b"XMP ", b"ICCP"];
+// ICC profile, EXIF and XMP flags of the VP8X chunk
+const WEBP_METADATA_FLAGS: u8 = 0x20 | 0x08 | 0x04;
+// Application extensions controlling how GIFs loop, which are kept
+const GIF_LOOP_APPLICATIONS: [&[u8; 11]; 2] = [b"NETSCAPE2.0", b"ANIMEXTS1.0"];
+
+pub fn output_dir() -> PathBuf {
+    std::env::temp_dir().join("qtm2").join("images")
+}
+
+type ProcessedImage = (PathBuf, anyhow::Result<(PathBuf, u64)>);
+
+/// Preprocesses images on the thread pool
+pub struct Preprocessor {
+    channel: (mpsc::Sender<ProcessedImage>, mpsc::Receiver<ProcessedImage>),
+}
+
+impl Default for Preprocessor {
+    fn default() -> Self {
+        Self {
+            channel: mpsc::channel(),
+        }
+    }
+}
+
+impl Preprocessor {
+    pub fn process(
+        &self,
+        image: &mut Image,
+        profile: &SiteProfile,
+        keep_metadata: bool,
+        ctx: &Context,
+    ) {
+        image.processed = None;
+        image.is_processing = true;
+
+        let path = image.path.clone();
+        let profile = profile.clone();
+        let sender = self.channel.0.clone();
+        let ctx = ctx.clone();
+        rayon::spawn(move || {
+            let result = preprocess(&path, &output_dir(), &profile, keep_metadata).and_then(
+                |processed_path| {
+                    let size = processed_path.metadata()?.len();
+                    Ok((processed_path, size))
+                },
+            );
+            // The receiver only disappears when the application is closing
+            let _ = sender.send((path, result));
+            ctx.request_repaint();
+        });
+    }
+
+    /// Return the error messages of the images that failed to be processed since the last call
+    pub fn poll(&self, images: &mut [Image]) -> Vec<String> {
+        let mut errors = Vec::new();
+        for (path, result) in self.channel.1.try_iter() {
+            let Some(image) = images.iter_mut().find(|image| image.path == path) else {
+                continue;
+            };
+            image.is_processing = false;
+            match result {
+                Ok(processed) => image.processed = Some(processed),
+                Err(err) => {
+                    warn!(?err, "Unable to preprocess {}", path.to_string_lossy());
+                    errors.push(format!("{}: {err}", image.filename));
+                }
+            }
+        }
+        errors
+    }
+}
+
+/// Write a copy of the image at `path` to `output_dir` that fits within the limits of `profile`
+///
+/// Images that already fit only have their metadata stripped, losslessly for JPEG, PNG, GIF and
+/// WebP. Otherwise JPEGs are re-encoded as JPEG and every other format as PNG, with the EXIF
+/// orientation applied; re-encoding always drops the metadata. Animated GIF, APNG and WebP images
+/// cannot be re-encoded without losing the animation, so they are rejected instead.
+pub fn preprocess(
+    path: &Path,
+    output_dir: &Path,
+    profile: &SiteProfile,
+    keep_metadata: bool,
+) -> anyhow::Result<PathBuf> {
+    let data = fs::read(path)?;
+    let format = image::guess_format(&data)?;
+    let is_animated = is_animated(&data, format)?;
+
+    let orientation = read_orientation(&data);
+    let (width, height) =
+        image::io::Reader::with_format(Cursor::new(&data), format).into_dimensions()?;
+    let is_within_limits = width <= profile.max_image_width
+        && height <= profile.max_image_height
+        && data.len() as u64 <= profile.max_image_size;
+
+    fs::create_dir_all(output_dir)?;
+    // Suffixed with a hash of the original path so that images with the same name do not collide
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let key = format!("{:x}", Sha256::digest(path.to_string_lossy().as_bytes()));
+    let output_path =
+        |extension: &str| output_dir.join(format!("{stem}-{}.{extension}", &key[..8]));
+
+    // Animations are never rotated, as that would mean re-encoding them
+    if is_within_limits && (keep_metadata || orientation == 1 || is_animated) {
+        let extension = path.extension().unwrap_or_default().to_string_lossy();
+        let data = match format {
+            _ if keep_metadata => data,
+            ImageFormat::Jpeg => strip_jpeg_metadata(&data)?,
+            ImageFormat::Png => strip_png_metadata(&data)?,
+            ImageFormat::Gif => strip_gif_metadata(&data)?,
+            ImageFormat::WebP => strip_webp_metadata(&data)?,
+            _ => {
+                return encode(
+                    image::load_from_memory(&data)?,
+                    format,
+                    profile,
+                    &output_path,
+                )
+            }
+        };
+        let output_path = output_path(&extension);
+        fs::write(&output_path, data)?;
+        return Ok(output_path);
+    }
+    if is_animated {
+        return Err(anyhow!(
+            "Animated images cannot be resized or recompressed without losing the animation; \
+             it is {width}x{height} and {}, but must be within {}x{} and {}",
+            ByteSize(data.len() as u64),
+            profile.max_image_width,
+            profile.max_image_height,
+            ByteSize(profile.max_image_size)
+        ));
+    }
+
+    if keep_metadata {
+        warn!("Metadata cannot be kept when an image has to be re-encoded");
+    }
+    let image = apply_orientation(image::load_from_memory(&data)?, orientation);
+    encode(image, format, profile, &output_path)
+}
+
+/// Re-encode `image` within the dimensions and file size limits of `profile`
+fn encode(
+    mut image: DynamicImage,
+    format: ImageFormat,
+    profile: &SiteProfile,
+    output_path: &dyn Fn(&str) -> PathBuf,
+) -> anyhow::Result<PathBuf> {
+    if image.width() > profile.max_image_width || image.height() > profile.max_image_height {
+        image = image.resize(
+            profile.max_image_width,
+            profile.max_image_height,
+            FilterType::Lanczos3,
+        );
+    }
+
+    let write = |data: Vec<u8>, extension: &str| -> anyhow::Result<PathBuf> {
+        let output_path = output_path(extension);
+        fs::write(&output_path, data)?;
+        Ok(output_path)
+    };
+    for _ in 0..MAX_DOWNSCALES {
+        if format == ImageFormat::Jpeg {
+            let rgb = image.to_rgb8();
+            for quality in JPEG_QUALITIES {
+                let mut buffer = Vec::new();
+                JpegEncoder::new_with_quality(&mut buffer, quality).encode_image(&rgb)?;
+                if buffer.len() as u64 <= profile.max_image_size {
+                    return write(buffer, "jpg");
+                }
+            }
+        } else {
+            let mut buffer = Cursor::new(Vec::new());
+            image.write_to(&mut buffer, ImageFormat::Png)?;
+            if buffer.get_ref().len() as u64 <= profile.max_image_size {
+                return write(buffer.into_inner(), "png");
+            }
+        }
+
+        image = image.resize(
+            (image.width() as f32 * DOWNSCALE_FACTOR) as u32,
+            (image.height() as f32 * DOWNSCALE_FACTOR) as u32,
+            FilterType::Lanczos3,
+        );
+    }
+    Err(anyhow!("Unable to fit the image within {} bytes", profile.max_image_size))
+}
+
+/// Return whether the image is an animated GIF, APNG or WebP
+fn is_animated(data: &[u8], format: ImageFormat) -> anyhow::Result<bool> {
+    Ok(match format {
+        ImageFormat::Gif => GifDecoder::new(Cursor::new(data))?.into_frames().take(2).count() > 1,
+        ImageFormat::Png => PngDecoder::new(Cursor::new(data))?.is_apng(),
+        ImageFormat::WebP => WebPDecoder::new(Cursor::new(data))?.has_animation(),
+        _ => false,
+    })
+}
+
+/// Return the EXIF orientation (1 to 8) of the image, or 1 if there is none
+fn read_orientation(data: &[u8]) -> u32 {
+    exif::Reader::new()
+        .read_from_container(&mut Cursor::new(data))
+        .ok()
+        .and_then(|exif| {
+            exif.get_field(exif::Tag::Orientation, exif::In::PRIMARY)?
+                .value
+                .get_uint(0)
+        })
+        .filter(|orientation| (1..=8).contains(orientation))
+        .unwrap_or(1)
+}
+
+fn apply_orientation(image: DynamicImage, orientation: u32) -> DynamicImage {
+    match orientation {
+        2 => image.fliph(),
+        3 => image.rotate180(),
+        4 => image.flipv(),
+        5 => image.rotate90().fliph(),
+        6 => image.rotate90(),
+        7 => image.rotate270().fliph(),
+        8 => image.rotate270(),
+        _ => image,
+    }
+}
+
+/// Remove the metadata segments of a JPEG without re-encoding it
+fn strip_jpeg_metadata(data: &[u8]) -> anyhow::Result<Vec<u8>> {
+    ensure!(data.starts_with(&[0xFF, 0xD8]), "Not a JPEG");
+    let mut output = vec![0xFF, 0xD8];
+    let mut index = 2;
+    loop {
+        // Skip fill bytes before a marker
+        while data.get(index + 1) == Some(&0xFF) {
+            index += 1;
+        }
+        ensure!(index + 4 <= data.len() && data[index] == 0xFF, "Malformed JPEG");
+        let marker = data[index + 1];
+        // Start of scan: the rest is entropy-coded image data
+        if marker == 0xDA {
+            output.extend_from_slice(&data[index..]);
+            return Ok(output);
+        }
+        let end = index + 2 + u16::from_be_bytes([data[index + 2], data[index + 3]]) as usize;
+        ensure!(end <= data.len(), "Truncated JPEG");
+        if !JPEG_METADATA_MARKERS.contains(&marker) {
+            output.extend_from_slice(&data[index..end]);
+        }
+        index = end;
+    }
+}
+
+/// Remove the metadata chunks of a PNG without re-encoding it
+fn strip_png_metadata(data: &[u8]) -> anyhow::Result<Vec<u8>> {
+    ensure!(data.starts_with(&PNG_SIGNATURE), "Not a PNG");
+    let mut output = PNG_SIGNATURE.to_vec();
+    let mut index = PNG_SIGNATURE.len();
+    while index < data.len() {
+        ensure!(index + 8 <= data.len(), "Malformed PNG");
+        let length = u32::from_be_bytes(data[index..index + 4].try_into()?) as usize;
+        // Length, type, data and CRC
+        let end = index + 12 + length;
+        ensure!(end <= data.len(), "Truncated PNG");
+        if !PNG_METADATA_CHUNKS.iter().any(|chunk| data[index + 4..index + 8] == chunk[..]) {
+            output.extend_from_slice(&data[index..end]);
+        }
+        index = end;
+    }
+    Ok(output)
+}
+
+/// Remove the comment and application extensions of a GIF without re-encoding it, except those
+/// controlling how it loops
+fn strip_gif_metadata(data: &[u8]) -> anyhow::Result<Vec<u8>> {
+    ensure!(
+        data.starts_with(b"GIF87a") || data.starts_with(b"GIF89a"),
+        "Not a GIF"
+    );
+    ensure!(data.len() >= 13, "Truncated GIF");
+    // Header, logical screen descriptor and global colour table
+    let mut index = 13 + colour_table_size(data[10]);
+    let mut output = data[..index.min(data.len())].to_vec();
+    loop {
+        let start = index;
+        match data.get(index) {
+            // Trailer
+            Some(0x3B) => {
+                output.push(0x3B);
+                return Ok(output);
+            }
+            // Extension
+            Some(0x21) => {
+                let label = *data
+                    .get(index + 1)
+                    .ok_or_else(|| anyhow!("Truncated GIF"))?;
+                index = skip_gif_sub_blocks(data, index + 2)?;
+                let is_metadata = match label {
+                    0xFE => true,
+                    0xFF => !GIF_LOOP_APPLICATIONS
+                        .iter()
+                        .any(|application| data[start + 2..].get(1..12) == Some(&application[..])),
+                    _ => false,
+                };
+                if !is_metadata {
+                    output.extend_from_slice(&data[start..index]);
+                }
+            }
+            // Image descriptor, local colour table, LZW code size and image data
+            Some(0x2C) => {
+                let packed = *data
+                    .get(index + 9)
+                    .ok_or_else(|| anyhow!("Truncated GIF"))?;
+                index = skip_gif_sub_blocks(data, index + 11 + colour_table_size(packed))?;
+                output.extend_from_slice(&data[start..index]);
+            }
+            _ => return Err(anyhow!("Malformed GIF")),
+        }
+    }
+}
+
+/// Return the size of the colour table following a GIF descriptor with `packed` fields
+fn colour_table_size(packed: u8) -> usize {
+    if packed & 0x80 == 0 {
+        0
+    } else {
+        3 << ((packed & 0x07) + 1)
+    }
+}
+
+/// Return the index after the sub-blocks of a GIF starting at `index`
+fn skip_gif_sub_blocks(data: &[u8], mut index: usize) -> anyhow::Result<usize> {
+    loop {
+        let size = *data.get(index).ok_or_else(|| anyhow!("Truncated GIF"))? as usize;
+        index += 1 + size;
+        if size == 0 {
+            return Ok(index);
+        }
+    }
+}
+
+/// Remove the metadata chunks of a WebP without re-encoding it
+fn strip_webp_metadata(data: &[u8]) -> anyhow::Result<Vec<u8>> {
+    ensure!(
+        data.len() >= 12 && data.starts_with(b"RIFF") && &data[8..12] == b"WEBP",
+        "Not a WebP"
+    );
+    let mut output = data[..12].to_vec();
+    let mut index = 12;
+    while index < data.len() {
+        ensure!(index + 8 <= data.len(), "Malformed WebP");
+        let length = u32::from_le_bytes(data[index + 4..index + 8].try_into()?) as usize;
+        // Type, length, data and padding to an even length
+        let end = index + 8 + length + length % 2;
+        ensure!(end <= data.len(), "Truncated WebP");
+        let chunk = &data[index..index + 4];
+        if chunk == b"VP8X" {
+            let flags_index = output.len() + 8;
+            output.extend_from_slice(&data[index..end]);
+            output[flags_index] &= !WEBP_METADATA_FLAGS;
+        } else if !WEBP_METADATA_CHUNKS
+            .iter()
+            .any(|metadata| chunk == &metadata[..])
+        {
+            output.extend_from_slice(&data[index..end]);
+        }
+        index = end;
+    }
+    let riff_length = (output.len() - 8) as u32;
+    output[4..8].copy_from_slice(&riff_length.to_le_bytes());
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use image::codecs::gif::GifEncoder;
+    use image::{Delay, Frame, Rgb, RgbImage, RgbaImage};
+
+    use super::*;
+
+    fn encode_gif(frame_count: usize) -> Vec<u8> {
+        let mut buffer = Vec::new();
+        let frames = (0..frame_count).map(|_| {
+            Frame::from_parts(
+                RgbaImage::new(8, 8),
+                0,
+                0,
+                Delay::from_numer_denom_ms(100, 1),
+            )
+        });
+        GifEncoder::new(&mut buffer).encode_frames(frames).unwrap();
+        buffer
+    }
+
+    fn encode_jpeg(image: &RgbImage) -> Vec<u8> {
+        let mut buffer = Vec::new();
+        JpegEncoder::new(&mut buffer).encode_image(image).unwrap();
+        buffer
+    }
+
+    #[test]
+    fn test_strip_metadata() {
+        let jpeg = encode_jpeg(&RgbImage::from_pixel(16, 16, Rgb([200, 100, 50])));
+        let app1 = [&[0xFF, 0xE1, 0x00, 0x0A][..], b"Exif\0\0GPS"].concat();
+        let with_exif = [&jpeg[..2], &app1, &jpeg[2..]].concat();
+        assert_eq!(strip_jpeg_metadata(&with_exif).unwrap(), jpeg);
+
+        let mut png = Cursor::new(Vec::new());
+        DynamicImage::new_rgb8(4, 4)
+            .write_to(&mut png, ImageFormat::Png)
+            .unwrap();
+        let png = png.into_inner();
+        // A tEXt chunk (with a dummy CRC) after the IHDR chunk
+        let text = [&[0, 0, 0, 7][..], b"tEXtGPS\0lat", &[0; 4]].concat();
+        let header_end = PNG_SIGNATURE.len() + 25;
+        let with_text = [&png[..header_end], &text, &png[header_end..]].concat();
+        assert_eq!(strip_png_metadata(&with_text).unwrap(), png);
+
+        assert!(strip_jpeg_metadata(&png).is_err());
+
+        // A comment extension before the trailer
+        let gif = encode_gif(2);
+        let comment = [&[0x21, 0xFE, 0x03][..], b"GPS", &[0x00]].concat();
+        let trailer = gif.len() - 1;
+        let with_comment = [&gif[..trailer], &comment, &gif[trailer..]].concat();
+        assert_eq!(strip_gif_metadata(&with_comment).unwrap(), gif);
+    }
+
+    #[test]
+    fn test_preprocess() {
+        let dir = tempfile::tempdir().unwrap();
+        let dir = dir.path();
+        let path = dir.join("large.jpg");
+        fs::write(&path, encode_jpeg(&RgbImage::new(3000, 1000))).unwrap();
+        let profile = SiteProfile {
+            max_image_width: 1200,
+            max_image_height: 1200,
+            max_image_size: 1_000_000,
+        };
+
+        let processed = preprocess(&path, &dir.join("output"), &profile, false).unwrap();
+        assert_eq!(processed.extension().unwrap(), "jpg");
+        assert_eq!(image::image_dimensions(&processed).unwrap(), (1200, 400));
+
+        // Already within the limits: only the metadata is stripped
+        let small = dir.join("small.jpg");
+        fs::write(&small, encode_jpeg(&RgbImage::new(100, 100))).unwrap();
+        let processed = preprocess(&small, &dir.join("output"), &profile, false).unwrap();
+        assert_eq!(fs::read(processed).unwrap(), fs::read(&small).unwrap());
+
+        // Animated images are not resized
+        let animation = dir.join("animation.gif");
+        fs::write(&animation, encode_gif(2)).unwrap();
+        let profile = SiteProfile {
+            max_image_width: 4,
+            ..profile
+        };
+        assert!(preprocess(&animation, &dir.join("output"), &profile, false).is_err());
+    }
+}
@@ -0,0 +1,124 @@
+// SPDX-License-Identifier: BSD-2-Clause-Patent
+
+use std::path::Path;
+
+use bytesize::ByteSize;
+use eframe::egui::{vec2, Align, Grid, Label, Layout, RichText, Ui};
+use walkdir::WalkDir;
+
+use crate::category::Category;
+use crate::image::Image;
+use crate::media::MediaInfo;
+use crate::tag::{Tag, TagData};
+
+// Thumbnails in the gallery are a quarter of the size of the hover preview
+const GALLERY_AREA_DIVISOR: usize = 4;
+
+/// Return the path (relative to the content) and size of every file in the content
+pub fn list_files<P: AsRef<Path>>(content_path: P) -> Vec<(String, u64)> {
+    let content_path = content_path.as_ref();
+    let root = content_path.parent().unwrap_or(content_path);
+    WalkDir::new(content_path)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_file())
+        .map(|entry| {
+            (
+                entry
+                    .path()
+                    .strip_prefix(root)
+                    .unwrap_or(entry.path())
+                    .to_string_lossy()
+                    .into_owned(),
+                entry.metadata().map(|m| m.len()).unwrap_or_default(),
+            )
+        })
+        .collect()
+}
+
+pub struct Preview<'a> {
+    pub title: &'a str,
+    pub categories: &'a [Category],
+    pub tags: Vec<&'a TagData>,
+    pub images: &'a [Image],
+    pub image_area: usize,
+    pub description: &'a str,
+    // `None` while the content is being listed
+    pub files: Option<&'a [(String, u64)]>,
+    pub media: &'a [(String, MediaInfo)],
+}
+
+impl<'a> Preview<'a> {
+    pub fn show(self, ui: &mut Ui) {
+        ui.heading(self.title);
+        ui.add_space(5.);
+
+        ui.label(
+            RichText::new(
+                self.categories
+                    .iter()
+                    .filter(|c| **c != Category::None)
+                    .map(|c| c.to_string())
+                    .collect::<Vec<String>>()
+                    .join(" › "),
+            )
+            .small(),
+        );
+        ui.add_space(10.);
+
+        ui.with_layout(Layout::left_to_right(Align::TOP).with_main_wrap(true), |ui| {
+            ui.style_mut().spacing.item_spacing = vec2(10., 10.);
+            for tag in self.tags {
+                ui.add(Tag::new(tag, &mut false));
+            }
+        });
+        ui.separator();
+
+        ui.with_layout(Layout::left_to_right(Align::TOP).with_main_wrap(true), |ui| {
+            for image in self.images {
+                match image.current_texture(ui.ctx()) {
+                    Some(texture_handle) => {
+                        ui.image(
+                            texture_handle,
+                            image.calculate_image_dimension(self.image_area / GALLERY_AREA_DIVISOR),
+                        )
+                        .on_hover_text(&image.filename);
+                    }
+                    None => {
+                        ui.monospace(&image.filename);
+                    }
+                }
+            }
+        });
+        ui.separator();
+
+        ui.add(Label::new(self.description).wrap(true));
+        ui.separator();
+
+        let Some(files) = self.files else {
+            ui.horizontal(|ui| {
+                ui.strong("Files");
+                ui.spinner();
+            });
+            return;
+        };
+        ui.strong(format!("Files ({})", files.len()));
+        Grid::new("preview_files")
+            .num_columns(2)
+            .striped(true)
+            .show(ui, |ui| {
+                for (path, size) in files {
+                    ui.monospace(path);
+                    ui.with_layout(Layout::right_to_left(Align::Center), |ui| {
+                        ui.monospace(ByteSize(*size).to_string());
+                    });
+                    ui.end_row();
+                    if let Some((_, info)) = self.media.iter().find(|(other, _)| other == path) {
+                        ui.label(RichText::new(info.summary()).small().weak());
+                        ui.end_row();
+                    }
+                }
+            });
+    }
+}
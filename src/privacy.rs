@@ -0,0 +1,352 @@
+// SPDX-License-Identifier: BSD-2-Clause-Patent
+
+use std::fs::File;
+use std::io::{BufReader, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+use exif::{In, Tag};
+use strum_macros::Display;
+use tracing::warn;
+use walkdir::WalkDir;
+
+const EXIF_EXTENSIONS: [&str; 9] = [
+    "jpg", "jpeg", "png", "webp", "tif", "tiff", "heic", "heif", "avif",
+];
+const QUICKTIME_EXTENSIONS: [&str; 5] = ["mp4", "m4v", "mov", "3gp", "3g2"];
+// XMP packets are near the start of a file; the rest is image data
+const XMP_SCAN_LIMIT: u64 = 4 * 1024 * 1024;
+// `moov` is normally at most a few megabytes; larger ones are assumed to be corrupt
+const MOOV_SIZE_LIMIT: u64 = 64 * 1024 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Display)]
+pub enum FindingKind {
+    Location,
+    #[strum(serialize = "Serial number")]
+    SerialNumber,
+    Owner,
+}
+
+/// A piece of potentially identifying metadata found in a file of the content
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Finding {
+    pub path: PathBuf,
+    pub kind: FindingKind,
+    pub detail: String,
+}
+
+/// Walk the content and list the identifying EXIF/XMP metadata of images and the metadata atoms
+/// of MP4/MOV files
+pub fn scan<P: AsRef<Path>>(content_path: P) -> Vec<Finding> {
+    WalkDir::new(content_path)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_file())
+        .flat_map(|entry| {
+            let path = entry.into_path();
+            let extension = path
+                .extension()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .to_lowercase();
+            let mut findings: Vec<(FindingKind, String)> = Vec::new();
+            if EXIF_EXTENSIONS.contains(&extension.as_str()) {
+                findings.extend(scan_exif(&path));
+            }
+            if EXIF_EXTENSIONS.contains(&extension.as_str())
+                || QUICKTIME_EXTENSIONS.contains(&extension.as_str())
+            {
+                findings.extend(scan_xmp(&path));
+            }
+            if QUICKTIME_EXTENSIONS.contains(&extension.as_str()) {
+                match scan_quicktime(&path) {
+                    Ok(quicktime_findings) => findings.extend(quicktime_findings),
+                    Err(err) => warn!(?err, "Unable to scan {}", path.to_string_lossy()),
+                }
+            }
+            findings.sort();
+            findings.dedup();
+            findings.into_iter().map(move |(kind, detail)| Finding {
+                path: path.clone(),
+                kind,
+                detail,
+            })
+        })
+        .collect()
+}
+
+fn scan_exif(path: &Path) -> Vec<(FindingKind, String)> {
+    let Ok(file) = File::open(path) else {
+        return Vec::new();
+    };
+    // Most images simply have no EXIF data
+    let Ok(exif) = exif::Reader::new().read_from_container(&mut BufReader::new(file)) else {
+        return Vec::new();
+    };
+
+    let value = |tag: Tag| {
+        exif.get_field(tag, In::PRIMARY).map(|field| {
+            field
+                .display_value()
+                .with_unit(&exif)
+                .to_string()
+                .trim_matches('"')
+                .to_owned()
+        })
+    };
+    let mut findings = Vec::new();
+    if let Some(latitude) = value(Tag::GPSLatitude) {
+        let longitude = value(Tag::GPSLongitude).unwrap_or_default();
+        findings.push((FindingKind::Location, format!("GPS {latitude}, {longitude}")));
+    }
+    for (tag, kind) in [
+        (Tag::BodySerialNumber, FindingKind::SerialNumber),
+        (Tag::LensSerialNumber, FindingKind::SerialNumber),
+        (Tag::ImageUniqueID, FindingKind::SerialNumber),
+        (Tag::CameraOwnerName, FindingKind::Owner),
+        (Tag::Artist, FindingKind::Owner),
+    ] {
+        if let Some(value) = value(tag).filter(|value| !value.trim().is_empty()) {
+            findings.push((kind, format!("{tag}: {value}")));
+        }
+    }
+    findings
+}
+
+fn scan_xmp(path: &Path) -> Vec<(FindingKind, String)> {
+    let mut data = Vec::new();
+    if let Err(err) =
+        File::open(path).and_then(|file| file.take(XMP_SCAN_LIMIT).read_to_end(&mut data))
+    {
+        warn!(?err, "Unable to read {}", path.to_string_lossy());
+        return Vec::new();
+    }
+    let data = String::from_utf8_lossy(&data);
+    let Some(start) = data.find("<x:xmpmeta") else {
+        return Vec::new();
+    };
+    let xmp = &data[start..];
+    let xmp = &xmp[..xmp.find("</x:xmpmeta>").unwrap_or(xmp.len())];
+
+    [
+        ("exif:GPSLatitude", FindingKind::Location),
+        ("exif:GPSLongitude", FindingKind::Location),
+        ("aux:SerialNumber", FindingKind::SerialNumber),
+        ("aux:LensSerialNumber", FindingKind::SerialNumber),
+        ("exifEX:BodySerialNumber", FindingKind::SerialNumber),
+        ("exifEX:CameraOwnerName", FindingKind::Owner),
+        ("dc:creator", FindingKind::Owner),
+        ("xmpRights:Owner", FindingKind::Owner),
+    ]
+    .into_iter()
+    .filter_map(|(property, kind)| {
+        xmp_property(xmp, property).map(|value| (kind, format!("XMP {property}: {value}")))
+    })
+    .collect()
+}
+
+/// Return the value of an XMP property written either as an attribute or as an element
+fn xmp_property(xmp: &str, property: &str) -> Option<String> {
+    if let Some(start) = xmp.find(&format!("{property}=\"")) {
+        let value = &xmp[start + property.len() + 2..];
+        return Some(value[..value.find('"')?].to_owned());
+    }
+
+    let start = xmp.find(&format!("<{property}"))?;
+    let element = &xmp[start..];
+    let content = &element[element.find('>')? + 1..element.find(&format!("</{property}>"))?];
+    // Strip nested `rdf:Seq`/`rdf:li` tags and join the texts
+    let mut text = String::new();
+    let mut is_in_tag = false;
+    for c in content.chars() {
+        match c {
+            '<' => {
+                is_in_tag = true;
+                text.push(' ');
+            }
+            '>' => is_in_tag = false,
+            _ if !is_in_tag => text.push(c),
+            _ => {}
+        }
+    }
+    let text = text.split_whitespace().collect::<Vec<&str>>().join(" ");
+    (!text.is_empty()).then_some(text)
+}
+
+/// Parse the metadata atoms in the `moov` box of an MP4/MOV file
+fn scan_quicktime(path: &Path) -> anyhow::Result<Vec<(FindingKind, String)>> {
+    let mut file = BufReader::new(File::open(path)?);
+    let length = file.get_ref().metadata()?.len();
+    let mut position = 0;
+    while position + 8 <= length {
+        file.seek(SeekFrom::Start(position))?;
+        let mut header = [0; 8];
+        file.read_exact(&mut header)?;
+        let (mut size, box_type) = (
+            u32::from_be_bytes(header[..4].try_into()?) as u64,
+            &header[4..],
+        );
+        let mut header_size = 8;
+        if size == 1 {
+            let mut large_size = [0; 8];
+            file.read_exact(&mut large_size)?;
+            size = u64::from_be_bytes(large_size);
+            header_size = 16;
+        } else if size == 0 {
+            size = length - position;
+        }
+        if size < header_size {
+            anyhow::bail!("Malformed box at {position}");
+        }
+
+        if box_type == b"moov" {
+            let payload_size = size - header_size;
+            if payload_size > MOOV_SIZE_LIMIT {
+                anyhow::bail!("moov box is too large");
+            }
+            let mut moov = vec![0; payload_size as usize];
+            file.read_exact(&mut moov)?;
+            let mut findings = Vec::new();
+            scan_boxes(&moov, &mut Vec::new(), &mut findings);
+            return Ok(findings);
+        }
+        position += size;
+    }
+    Ok(Vec::new())
+}
+
+/// Iterate over the `(type, payload)` of the boxes in `data`
+fn boxes(data: &[u8]) -> impl Iterator<Item = (&[u8], &[u8])> {
+    let mut rest = data;
+    std::iter::from_fn(move || {
+        if rest.len() < 8 {
+            return None;
+        }
+        let size = u32::from_be_bytes(rest[..4].try_into().ok()?) as usize;
+        let size = if size == 0 { rest.len() } else { size };
+        if size < 8 || size > rest.len() {
+            return None;
+        }
+        let (current, next) = rest.split_at(size);
+        rest = next;
+        Some((&current[4..8], &current[8..]))
+    })
+}
+
+fn scan_boxes(data: &[u8], keys: &mut Vec<String>, findings: &mut Vec<(FindingKind, String)>) {
+    for (box_type, payload) in boxes(data) {
+        match box_type {
+            b"trak" | b"udta" | b"ilst" => scan_boxes(payload, keys, findings),
+            // An ISO `meta` box is a full box with 4 bytes of version and flags; a QuickTime
+            // one is not
+            b"meta" => {
+                let is_full_box = payload.get(4..8) != Some(b"hdlr".as_slice())
+                    && payload.get(..4) == Some([0; 4].as_slice());
+                let payload = if is_full_box { &payload[4..] } else { payload };
+                scan_boxes(payload, keys, findings);
+            }
+            // Names of the metadata items in `ilst`, which are referred to by 1-based index
+            b"keys" => {
+                *keys = boxes(payload.get(8..).unwrap_or_default())
+                    .map(|(_, key)| String::from_utf8_lossy(key).into_owned())
+                    .collect();
+            }
+            _ => {
+                // Box types are Latin-1, e.g. `©xyz`
+                let name = match u32::from_be_bytes(box_type.try_into().unwrap()) as usize {
+                    index @ 1.. if index <= keys.len() => keys[index - 1].clone(),
+                    _ => box_type.iter().map(|byte| *byte as char).collect(),
+                };
+                if let Some(kind) = quicktime_finding_kind(&name) {
+                    findings.push((kind, format!("{name}: {}", quicktime_value(payload))));
+                }
+            }
+        }
+    }
+}
+
+fn quicktime_finding_kind(name: &str) -> Option<FindingKind> {
+    match name {
+        "©xyz" | "com.apple.quicktime.location.ISO6709" => Some(FindingKind::Location),
+        "©ART" | "©aut" | "com.apple.quicktime.author" => Some(FindingKind::Owner),
+        // The make and model of the camera are in almost every photo and video, and are not
+        // identifying on their own
+        _ => None,
+    }
+}
+
+/// Return the text of a metadata item, either in a nested `data` box or as a QuickTime user data
+/// string (2 bytes of length and 2 bytes of language code)
+fn quicktime_value(payload: &[u8]) -> String {
+    let value = match boxes(payload).find(|(box_type, _)| *box_type == b"data") {
+        // 4 bytes of type and 4 bytes of locale
+        Some((_, data)) => data.get(8..).unwrap_or_default(),
+        None => payload.get(4..).unwrap_or_default(),
+    };
+    String::from_utf8_lossy(value).trim_matches('\0').trim().to_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mp4_box(box_type: &[u8], payload: &[u8]) -> Vec<u8> {
+        [&((payload.len() + 8) as u32).to_be_bytes()[..], box_type, payload].concat()
+    }
+
+    #[test]
+    fn test_scan_quicktime() {
+        let location = [&[0, 18, 0x15, 0xC7][..], b"+51.5007-000.1246/"].concat();
+        let udta = mp4_box(b"udta", &mp4_box(b"\xa9xyz", &location));
+        let keys = mp4_box(
+            b"keys",
+            &[&[0; 4][..], &1u32.to_be_bytes(), &mp4_box(b"mdta", b"com.apple.quicktime.model")]
+                .concat(),
+        );
+        let data = mp4_box(b"data", &[&[0, 0, 0, 1, 0, 0, 0, 0][..], b"iPhone 15"].concat());
+        let ilst = mp4_box(b"ilst", &mp4_box(&1u32.to_be_bytes(), &data));
+        let meta = mp4_box(b"meta", &[mp4_box(b"hdlr", &[0; 25]), keys, ilst].concat());
+        let moov = mp4_box(b"moov", &[udta, meta].concat());
+
+        let dir = tempfile::tempdir().unwrap();
+        let dir = dir.path();
+        std::fs::write(
+            dir.join("video.mov"),
+            [mp4_box(b"ftyp", b"qt  "), mp4_box(b"mdat", &[0; 64]), moov].concat(),
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join("photo.jpg"),
+            r#"<x:xmpmeta><rdf:Description exif:GPSLatitude="51,30.04N"><dc:creator><rdf:Seq><rdf:li>Jane Doe</rdf:li></rdf:Seq></dc:creator></rdf:Description></x:xmpmeta>"#,
+        )
+        .unwrap();
+
+        let findings: Vec<(String, FindingKind, String)> = scan(dir)
+            .into_iter()
+            .map(|finding| {
+                let name = finding.path.file_name().unwrap().to_string_lossy().into_owned();
+                (name, finding.kind, finding.detail)
+            })
+            .collect();
+        assert_eq!(
+            findings,
+            [
+                (
+                    "photo.jpg".to_owned(),
+                    FindingKind::Location,
+                    "XMP exif:GPSLatitude: 51,30.04N".to_owned()
+                ),
+                (
+                    "photo.jpg".to_owned(),
+                    FindingKind::Owner,
+                    "XMP dc:creator: Jane Doe".to_owned()
+                ),
+                (
+                    "video.mov".to_owned(),
+                    FindingKind::Location,
+                    "©xyz: +51.5007-000.1246/".to_owned()
+                ),
+            ]
+        );
+    }
+}
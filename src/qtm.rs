@@ -3,30 +3,85 @@
 use std::borrow::Cow;
 use std::collections::BTreeMap;
 use std::fs;
-use std::path::PathBuf;
-use std::sync::mpsc;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::{mpsc, Arc};
 use std::sync::mpsc::TryRecvError;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
 
 use bytesize::ByteSize;
+use rayon::prelude::*;
+use reqwest::blocking::Client;
 use eframe::egui;
 use eframe::egui::{
-    Align, Context, Frame, Grid, Id, Layout, Margin, Rounding, ScrollArea, show_tooltip, TextStyle,
-    vec2, widgets,
+    Align, Align2, Color32, Context, Frame, Grid, Id, Key, LayerId, Layout, Margin, Modifiers,
+    Order, ProgressBar, Rounding, ScrollArea, show_tooltip, TextStyle, Ui, vec2, widgets,
 };
 use strum::IntoEnumIterator;
 use tracing::{info, warn};
 
 use crate::{
-    cache_dir, config_local_dir, data_local_dir, DialogMessage, file_dialog, get_style_by_theme,
-    initialise_dirs, selectable_table, set_context,
+    cache_dir, clipboard, config_local_dir, data_local_dir, DialogMessage, file_dialog,
+    get_style_by_theme, initialise_dirs, privacy, selectable_table, set_context,
 };
-use crate::category::Category;
-use crate::file_dialog::select_content;
-use crate::image::Image;
+use crate::category::{filter_categories, top_match, Category, SiteCategories};
+use crate::clipboard::Pasted;
+use crate::draft::{draft_path, Draft, AUTOSAVE_INTERVAL};
+use crate::file_dialog::{content_from_path, load_new_images, select_content, select_tag_file};
+use crate::history::{category_usage, HistoryEntry};
+use crate::image::{
+    check_ffmpeg, generate_video_thumbnail_image, is_supported_image, list_videos, DuplicateKind,
+    Image, ImageLoader,
+};
+use crate::image_host::{ImageUploader, MultipartHost};
+use crate::media::{probe, MediaInfo};
+use crate::preprocess::{output_dir, Preprocessor};
+use crate::preview::{list_files, Preview};
+use crate::privacy::Finding;
 use crate::qtm_config::{QtmConfig, QtmTheme};
-use crate::selectable_table::{Column, TableBuilder};
-use crate::tag::{Tag, TagColor, TagData};
+use crate::selectable_table::{
+    clear_sort, move_rows, Column, Selection, Sort, SortDirection, TableBuilder, TableEvent,
+};
+use crate::suggestion::{tokenise_content, CachedSuggestions};
+use crate::tag::{fuzzy_score, CustomTag, Tag, TagColor, TagData, TagGrouping, TagSyncOutcome};
+use crate::template::{Template, TemplateValues, PLACEHOLDERS};
+use crate::viewer::{ImageViewer, ViewerAction};
+use crate::validation::{
+    has_errors, Issue, Severity, validate_categories, validate_content, validate_description,
+    validate_title,
+};
 use crate::torrent::create_torrent_file;
+use crate::upload_queue::{JobState, UploadJob, UploadQueue};
+
+const RECENT_TAG_COUNT: usize = 8;
+const DUPLICATE_THUMBNAIL_AREA: usize = 100 * 100;
+
+type ProbedContent = (PathBuf, Vec<(String, MediaInfo)>);
+type ListedFiles = (PathBuf, Vec<(String, u64)>);
+// The content with the issues and tokens of its file names
+type ScannedContent = ((PathBuf, String, u64), Vec<Issue>, Vec<String>);
+type LoadedContent = (PathBuf, anyhow::Result<ScannedContent>);
+
+// Lists fetched from the site in the background
+enum Synced {
+    Tags(Vec<TagData>),
+    Categories(SiteCategories),
+}
+
+// Clicked in the upload queue window
+enum JobAction {
+    Retry,
+    Skip,
+    Remove,
+}
+
+/// An added image with the same content as an image already in the list
+struct DuplicateImage {
+    path: PathBuf,
+    original: PathBuf,
+    kind: DuplicateKind,
+    is_kept: bool,
+}
 
 pub struct Qtm {
     config: QtmConfig,
@@ -36,46 +91,1738 @@ pub struct Qtm {
 
     is_file: bool,
     content: Option<(PathBuf, String, u64)>,
+    content_issues: Vec<Issue>,
+    content_tokens: Vec<String>,
+    // Dropped or selected content which is still being scanned in the background
+    pending_content: Option<PathBuf>,
+    content_channel: (mpsc::Sender<LoadedContent>, mpsc::Receiver<LoadedContent>),
 
     categories: [Category; 5],
+    site_categories: SiteCategories,
+    category_usage: BTreeMap<String, usize>,
+    category_query: String,
+    // Slot of the category drop-down that `category_query` was typed into
+    category_query_slot: Option<usize>,
 
     images: Vec<Image>,
-    selected_index: Option<usize>,
+    image_loader: ImageLoader,
+    preprocessor: Preprocessor,
+    // Sorted indices of the images selected in the table
+    selected_images: Vec<usize>,
 
     title: String,
     description: String,
 
-    tags: BTreeMap<TagData, bool>,
-    is_tag_menu_open: bool,
-    new_custom_tag: TagData,
-    custom_tags: BTreeMap<TagData, bool>,
-}
+    tags: BTreeMap<TagData, bool>,
+    is_tag_menu_open: bool,
+    new_custom_tag: TagData,
+    custom_tags: BTreeMap<TagData, bool>,
+    custom_tag_usage: BTreeMap<String, u64>,
+    tag_query: String,
+    tag_grouping: TagGrouping,
+    tag_cursor: usize,
+    // Tag texts and category names the user has declined as suggestions for the current upload
+    dismissed_suggestions: Vec<String>,
+    suggestions: CachedSuggestions,
+
+    draft_name: String,
+    new_draft_name: String,
+    // An existing draft which "Save as" would overwrite, awaiting confirmation
+    draft_to_overwrite: Option<String>,
+    last_saved_draft: Draft,
+    last_autosave: Instant,
+    // Whether the config has frequent changes, such as the recent tags, which are saved with the
+    // next autosave rather than immediately
+    is_config_dirty: bool,
+    is_autosave_enabled: bool,
+    is_draft_menu_open: bool,
+    is_restore_prompt: bool,
+
+    templates: Vec<Template>,
+    // A template picked while the description was not empty, awaiting confirmation
+    template_to_apply: Option<Template>,
+    is_template_menu_open: bool,
+    selected_template: Option<usize>,
+    template_buffer: Template,
+
+    is_preview_open: bool,
+    // `None` while the content is being listed in the background
+    preview_files: Option<Vec<(String, u64)>>,
+    preview_files_channel: (mpsc::Sender<ListedFiles>, mpsc::Receiver<ListedFiles>),
+
+    privacy_channel: (mpsc::Sender<Vec<Finding>>, mpsc::Receiver<Vec<Finding>>),
+    privacy_findings: Option<Vec<Finding>>,
+
+    contact_sheet_channel: (mpsc::Sender<PathBuf>, mpsc::Receiver<PathBuf>),
+    // The drafts of uploads whose torrent has been created
+    history_channel: (mpsc::Sender<Draft>, mpsc::Receiver<Draft>),
+
+    // Details of the videos in the content by their paths relative to the content's parent
+    media_info: Vec<(String, MediaInfo)>,
+    media_channel: (mpsc::Sender<ProbedContent>, mpsc::Receiver<ProbedContent>),
+
+    // Where pasted images are saved during this session
+    paste_dir: PathBuf,
+
+    image_viewer: Option<ImageViewer>,
+
+    // Images the user has just added, which are checked for duplicates once loaded
+    new_images: Vec<PathBuf>,
+    // Awaiting the user's decision to keep or drop them
+    duplicates: Vec<DuplicateImage>,
+    // Images which were already in the list when added again
+    skipped_images: Vec<PathBuf>,
+
+    image_uploader: ImageUploader,
+
+    upload_queue: UploadQueue,
+    is_queue_open: bool,
+    // Whether the upload being scanned for private metadata is to be queued
+    is_queueing: bool,
+    sync_channel: (mpsc::Sender<Synced>, mpsc::Receiver<Synced>),
+}
+
+impl Qtm {
+    pub fn new(
+        cc: &eframe::CreationContext<'_>,
+        config: QtmConfig,
+        tags: Vec<TagData>,
+        site_categories: SiteCategories,
+        client: Client,
+    ) -> Self {
+        info!("Started Main Application");
+        set_context(cc, config.theme);
+
+        let custom_tags = TagData::fetch_custom(config_local_dir("custom_tags.json"));
+
+        let history = HistoryEntry::load(data_local_dir("history.json"));
+
+        // Offer to restore drafts left over by a previous session
+        let drafts = Draft::list(cache_dir("drafts"));
+        let has_drafts = !drafts.is_empty();
+
+        let upload_queue = UploadQueue::new(data_local_dir("queue.json"), data_local_dir("queue"));
+        let paste_dir = cache_dir("pasted").join(
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_secs()
+                .to_string(),
+        );
+        let referenced_images: Vec<PathBuf> = drafts
+            .iter()
+            .filter_map(|name| Draft::load(draft_path(name)))
+            .flat_map(|draft| draft.images)
+            .chain(upload_queue.jobs.iter().flat_map(|job| {
+                job.draft.images.iter().chain(job.image_paths.iter()).cloned()
+            }))
+            .collect();
+        clipboard::remove_stale_paste_dirs(&cache_dir("pasted"), &paste_dir, &referenced_images);
+
+        let qtm = Self {
+            config,
+            dialog: None,
+            dialog_channel: mpsc::channel(),
+            is_file: true,
+            content: None,
+            content_issues: Vec::new(),
+            content_tokens: Vec::new(),
+            pending_content: None,
+            content_channel: mpsc::channel(),
+            categories: [Category::None; 5],
+            site_categories,
+            category_usage: category_usage(&history),
+            category_query: "".to_owned(),
+            category_query_slot: None,
+            images: Vec::new(),
+            image_loader: ImageLoader::new(cache_dir("thumbnails")),
+            preprocessor: Preprocessor::default(),
+            selected_images: Vec::new(),
+            title: "".to_owned(),
+            description: "".to_owned(),
+            tags: tags.into_iter().map(|tag| (tag, false)).collect(),
+            is_tag_menu_open: false,
+            new_custom_tag: TagData {
+                text: "".to_owned(),
+                color: TagColor::BlueGrey,
+                aliases: Vec::new(),
+            },
+            tag_query: "".to_owned(),
+            tag_grouping: TagGrouping::Source,
+            tag_cursor: 0,
+            dismissed_suggestions: Vec::new(),
+            suggestions: CachedSuggestions::default(),
+            custom_tag_usage: custom_tags
+                .iter()
+                .map(|tag| (tag.data.text.clone(), tag.usage))
+                .collect(),
+            custom_tags: custom_tags
+                .into_iter()
+                .map(|tag| (tag.data, false))
+                .collect(),
+            draft_name: Draft::generate_name(),
+            new_draft_name: "".to_owned(),
+            draft_to_overwrite: None,
+            last_saved_draft: Draft::default(),
+            last_autosave: Instant::now(),
+            is_config_dirty: false,
+            is_autosave_enabled: true,
+            is_draft_menu_open: has_drafts,
+            is_restore_prompt: has_drafts,
+            templates: Template::load(config_local_dir("templates.json")),
+            template_to_apply: None,
+            is_template_menu_open: false,
+            selected_template: None,
+            template_buffer: Template::new(""),
+            is_preview_open: false,
+            preview_files: None,
+            preview_files_channel: mpsc::channel(),
+            privacy_channel: mpsc::channel(),
+            privacy_findings: None,
+            contact_sheet_channel: mpsc::channel(),
+            history_channel: mpsc::channel(),
+            media_info: Vec::new(),
+            media_channel: mpsc::channel(),
+            paste_dir,
+            image_viewer: None,
+            new_images: Vec::new(),
+            duplicates: Vec::new(),
+            skipped_images: Vec::new(),
+            image_uploader: ImageUploader::default(),
+            upload_queue,
+            is_queue_open: false,
+            is_queueing: false,
+            sync_channel: mpsc::channel(),
+        };
+        qtm.sync(client, &cc.egui_ctx);
+        qtm
+    }
+
+    /// Sync the tags and categories with the site in the background, so that startup is not
+    /// held up; the cached ones are used until then
+    fn sync(&self, client: Client, ctx: &Context) {
+        let tag_source_url = self.config.tag_source_url.clone();
+        let category_source_url = self.config.category_source_url.clone();
+        let sender = self.sync_channel.0.clone();
+        let ctx = ctx.clone();
+        std::thread::spawn(move || {
+            // The receiver only disappears when the application is closing
+            match TagData::sync(&client, &tag_source_url, cache_dir("tags.json")) {
+                Ok(TagSyncOutcome::Updated { .. }) => {
+                    let _ = sender.send(Synced::Tags(TagData::fetch_data(cache_dir("tags.json"))));
+                    ctx.request_repaint();
+                }
+                Ok(TagSyncOutcome::Unchanged) => {}
+                Err(err) => warn!(?err, "Unable to sync tags; using cached tags"),
+            }
+            match SiteCategories::sync(&client, &category_source_url, cache_dir("categories.json")) {
+                Ok(categories) => {
+                    let _ = sender.send(Synced::Categories(categories));
+                    ctx.request_repaint();
+                }
+                Err(err) => warn!(?err, "Unable to fetch categories; using cached categories"),
+            }
+        });
+    }
+
+    fn save_custom_tags(&self) {
+        let tags: Vec<CustomTag> = self
+            .custom_tags
+            .keys()
+            .map(|data| CustomTag {
+                data: data.clone(),
+                usage: self
+                    .custom_tag_usage
+                    .get(&data.text)
+                    .copied()
+                    .unwrap_or_default(),
+            })
+            .collect();
+        TagData::save_custom(config_local_dir("custom_tags.json"), &tags);
+    }
+
+    /// Merge a shared custom tag set into the local one; imported colours take precedence
+    fn import_custom_tags(&mut self) {
+        let Some(path) = select_tag_file(false, self.config.default_directory.as_deref()) else {
+            return;
+        };
+        match TagData::import_custom(&path) {
+            Ok(tags) => {
+                TagData::merge_custom(&mut self.custom_tags, &mut self.custom_tag_usage, tags);
+                self.save_custom_tags();
+            }
+            Err(err) => {
+                warn!(?err, "Unable to import custom tags");
+                self.dialog_channel
+                    .0
+                    .send(DialogMessage(
+                        Cow::Borrowed("Unable to import custom tags\n\nCheck log for more information."),
+                        true,
+                    ))
+                    .unwrap();
+            }
+        }
+    }
+
+    fn export_custom_tags(&self) {
+        if let Some(path) = select_tag_file(true, self.config.default_directory.as_deref()) {
+            info!("Exporting custom tags to {}", path.to_string_lossy());
+            let tags: Vec<CustomTag> = self
+                .custom_tags
+                .keys()
+                .map(|data| CustomTag {
+                    data: data.clone(),
+                    usage: 0,
+                })
+                .collect();
+            TagData::save_custom(path, &tags);
+        }
+    }
+
+    fn toggle_tag(&mut self, is_custom: bool, data: &TagData) {
+        let tags = if is_custom {
+            &mut self.custom_tags
+        } else {
+            &mut self.tags
+        };
+        let Some(is_selected) = tags.get_mut(data) else {
+            return;
+        };
+        *is_selected = !*is_selected;
+
+        if *is_selected {
+            self.config.recent_tags.retain(|text| *text != data.text);
+            self.config.recent_tags.insert(0, data.text.clone());
+            self.config.recent_tags.truncate(RECENT_TAG_COUNT);
+            self.is_config_dirty = true;
+            if is_custom {
+                *self.custom_tag_usage.entry(data.text.clone()).or_default() += 1;
+            }
+        }
+        if is_custom {
+            self.save_custom_tags();
+        }
+    }
+
+    /// Return the titled sections of (is_custom, tag) shown in the tag menu
+    fn tag_sections(&self) -> Vec<(String, Vec<(bool, TagData)>)> {
+        let all_tags = self
+            .tags
+            .keys()
+            .map(|data| (false, data))
+            .chain(self.custom_tags.keys().map(|data| (true, data)));
+
+        if !self.tag_query.trim().is_empty() {
+            let mut results: Vec<(i64, (bool, TagData))> = all_tags
+                .filter_map(|(is_custom, data)| {
+                    std::iter::once(&data.text)
+                        .chain(data.aliases.iter())
+                        .filter_map(|text| fuzzy_score(&self.tag_query, text))
+                        .max()
+                        .map(|score| (score, (is_custom, data.clone())))
+                })
+                .collect();
+            results.sort_by(|a, b| b.0.cmp(&a.0).then_with(|| a.1 .1.cmp(&b.1 .1)));
+            return vec![(
+                "Results".to_owned(),
+                results.into_iter().map(|(_, tag)| tag).collect(),
+            )];
+        }
+
+        let mut sections: Vec<(String, Vec<(bool, TagData)>)> = vec![(
+            "Recently used".to_owned(),
+            self.config
+                .recent_tags
+                .iter()
+                .filter_map(|text| {
+                    all_tags
+                        .clone()
+                        .find(|(_, data)| data.text == *text)
+                        .map(|(is_custom, data)| (is_custom, data.clone()))
+                })
+                .collect(),
+        )];
+        match self.tag_grouping {
+            TagGrouping::Source => {
+                for (title, is_custom) in [("Official", false), ("Custom", true)] {
+                    sections.push((
+                        title.to_owned(),
+                        all_tags
+                            .clone()
+                            .filter(|(is_custom_tag, _)| *is_custom_tag == is_custom)
+                            .map(|(is_custom, data)| (is_custom, data.clone()))
+                            .collect(),
+                    ));
+                }
+            }
+            TagGrouping::Color => {
+                for color in TagColor::iter() {
+                    sections.push((
+                        color.to_string(),
+                        all_tags
+                            .clone()
+                            .filter(|(_, data)| data.color == color)
+                            .map(|(is_custom, data)| (is_custom, data.clone()))
+                            .collect(),
+                    ));
+                }
+            }
+        }
+        sections.retain(|(_, tags)| !tags.is_empty());
+        sections
+    }
+
+    /// Search box and tag sections of the tag menu
+    ///
+    /// Arrow keys move the cursor between tags (left/right only when not typing),
+    /// Enter toggles the tag under the cursor and Escape closes the menu.
+    fn show_tag_list(&mut self, ui: &mut Ui) {
+        let search_id = Id::new("tag_query");
+        let focus = ui.memory(|m| m.focus());
+        let mut cursor_delta: isize = 0;
+        let mut is_toggling = false;
+        if focus.is_none() || focus == Some(search_id) {
+            ui.input_mut(|i| {
+                if i.consume_key(Modifiers::NONE, Key::ArrowDown) {
+                    cursor_delta += 1;
+                }
+                if i.consume_key(Modifiers::NONE, Key::ArrowUp) {
+                    cursor_delta -= 1;
+                }
+                if focus.is_none() && i.consume_key(Modifiers::NONE, Key::ArrowRight) {
+                    cursor_delta += 1;
+                }
+                if focus.is_none() && i.consume_key(Modifiers::NONE, Key::ArrowLeft) {
+                    cursor_delta -= 1;
+                }
+                is_toggling = i.consume_key(Modifiers::NONE, Key::Enter);
+                if i.consume_key(Modifiers::NONE, Key::Escape) {
+                    self.is_tag_menu_open = false;
+                }
+            });
+        }
+
+        ui.horizontal(|ui| {
+            if ui
+                .add(
+                    widgets::TextEdit::singleline(&mut self.tag_query)
+                        .id(search_id)
+                        .desired_width(250.)
+                        .hint_text("Search tags"),
+                )
+                .changed()
+            {
+                self.tag_cursor = 0;
+            }
+            egui::ComboBox::from_id_source("tag_grouping")
+                .selected_text(format!("By {}", self.tag_grouping).to_lowercase())
+                .width(120.)
+                .show_ui(ui, |ui| {
+                    for grouping in TagGrouping::iter() {
+                        ui.selectable_value(
+                            &mut self.tag_grouping,
+                            grouping,
+                            format!("By {grouping}").to_lowercase(),
+                        );
+                    }
+                });
+        });
+        ui.add_space(5.);
+
+        let sections = self.tag_sections();
+        let count: usize = sections.iter().map(|(_, tags)| tags.len()).sum();
+        if count > 0 {
+            self.tag_cursor =
+                (self.tag_cursor as isize + cursor_delta).rem_euclid(count as isize) as usize;
+        }
+
+        let mut toggled = None;
+        let mut removed = None;
+        ScrollArea::vertical()
+            .max_height(250.)
+            .auto_shrink([false; 2])
+            .show(ui, |ui| {
+                let mut index = 0;
+                for (title, tags) in sections.iter() {
+                    ui.small(title);
+                    ui.with_layout(Layout::left_to_right(Align::TOP).with_main_wrap(true), |ui| {
+                        ui.style_mut().spacing.item_spacing = vec2(10., 10.);
+                        for (is_custom, data) in tags {
+                            let mut is_selected = if *is_custom {
+                                self.custom_tags.get(data)
+                            } else {
+                                self.tags.get(data)
+                            }
+                            .copied()
+                            .unwrap_or_default();
+                            let response = ui.add(Tag::new(data, &mut is_selected));
+                            if index == self.tag_cursor {
+                                ui.painter().rect_stroke(
+                                    response.rect.expand(3.),
+                                    Rounding::same(20.),
+                                    ui.visuals().selection.stroke,
+                                );
+                                if cursor_delta != 0 {
+                                    response.scroll_to_me(None);
+                                }
+                                if is_toggling {
+                                    toggled = Some((*is_custom, data.clone()));
+                                }
+                            }
+                            if response.clicked() {
+                                self.tag_cursor = index;
+                                toggled = Some((*is_custom, data.clone()));
+                            }
+                            if *is_custom && response.secondary_clicked() {
+                                removed = Some(data.clone());
+                            }
+                            index += 1;
+                        }
+                    });
+                    ui.add_space(5.);
+                }
+            });
+
+        if let Some((is_custom, data)) = toggled {
+            self.toggle_tag(is_custom, &data);
+        }
+        if let Some(data) = removed {
+            self.custom_tags.remove(&data);
+            self.custom_tag_usage.remove(&data.text);
+            self.config.recent_tags.retain(|text| *text != data.text);
+            self.save_custom_tags();
+        }
+    }
+
+    /// Chips of the suggested tags and categories that have not been applied or dismissed;
+    /// a click applies the suggestion and a secondary click dismisses it
+    fn show_suggestions(&mut self, ui: &mut Ui) {
+        let suggestions = self.suggestions.get(
+            &self.content_tokens,
+            &self.title,
+            self.tags.keys().chain(self.custom_tags.keys()),
+            &self.config.category_keywords,
+        );
+        let tags: Vec<TagData> = suggestions
+            .tags
+            .iter()
+            .filter(|data| {
+                let is_selected = self.tags.get(data).or_else(|| self.custom_tags.get(data));
+                !is_selected.copied().unwrap_or(false)
+                    && !self.dismissed_suggestions.contains(&data.text)
+            })
+            .cloned()
+            .collect();
+        let categories: Vec<Category> = suggestions
+            .categories
+            .iter()
+            .copied()
+            .filter(|category| {
+                !self.categories.contains(category)
+                    && self.site_categories.offers(*category)
+                    && !self.dismissed_suggestions.contains(&category.to_string())
+            })
+            .collect();
+        if tags.is_empty() && categories.is_empty() {
+            ui.weak("None");
+            return;
+        }
+
+        ui.with_layout(Layout::left_to_right(Align::TOP).with_main_wrap(true), |ui| {
+            ui.style_mut().spacing.item_spacing = vec2(10., 10.);
+            for category in categories {
+                let Some(empty_slot) = self.categories.iter().position(|c| *c == Category::None)
+                else {
+                    break;
+                };
+                let response = ui
+                    .add(
+                        widgets::Button::new(
+                            egui::RichText::new(format!("📂 {category}")).small(),
+                        )
+                        .rounding(Rounding::same(20.)),
+                    )
+                    .on_hover_text("Click to add this category, right-click to dismiss");
+                if response.clicked() {
+                    self.categories[empty_slot] = category;
+                }
+                if response.secondary_clicked() {
+                    self.dismissed_suggestions.push(category.to_string());
+                }
+            }
+            for data in tags {
+                let response = ui
+                    .add(Tag::new(&data, &mut false))
+                    .on_hover_text("Click to add this tag, right-click to dismiss");
+                if response.clicked() {
+                    let is_custom = self.custom_tags.contains_key(&data);
+                    self.toggle_tag(is_custom, &data);
+                }
+                if response.secondary_clicked() {
+                    self.dismissed_suggestions.push(data.text.clone());
+                }
+            }
+        });
+    }
+
+    fn is_main_ui_enabled(&self) -> bool {
+        self.dialog.is_none()
+            && !self.is_tag_menu_open
+            && !self.is_draft_menu_open
+            && !self.is_template_menu_open
+            && !self.is_preview_open
+            && self.privacy_findings.is_none()
+            && self.image_viewer.is_none()
+            && self.duplicates.is_empty()
+            && self.skipped_images.is_empty()
+            && !self.is_queue_open
+    }
+
+    /// Append images the user has just added, which are checked for duplicates once loaded, and
+    /// report the `skipped` ones which were already in the list
+    fn add_images(&mut self, mut images: Vec<Image>, skipped: Vec<PathBuf>) {
+        self.new_images
+            .extend(images.iter().map(|image| image.path.clone()));
+        self.images.append(&mut images);
+        self.skipped_images.extend(skipped);
+    }
+
+    /// Compare an image the user has just added with the images already in the list, queueing it
+    /// for review if it duplicates one of them
+    fn check_duplicate(&mut self, path: &Path) {
+        let Some(index) = self.new_images.iter().position(|new_image| new_image == path) else {
+            return;
+        };
+        self.new_images.remove(index);
+        let Some(hashes) = self
+            .images
+            .iter()
+            .find(|image| image.path == path)
+            .and_then(|image| image.hashes)
+        else {
+            return;
+        };
+        let duplicate = self
+            .images
+            .iter()
+            .filter(|image| {
+                image.path != path
+                    && !self.new_images.contains(&image.path)
+                    && !self.duplicates.iter().any(|duplicate| duplicate.path == image.path)
+            })
+            .find_map(|image| Some((image.path.clone(), hashes.compare(&image.hashes?)?)));
+        if let Some((original, kind)) = duplicate {
+            info!(?kind, "Found a duplicate image: {}", path.to_string_lossy());
+            self.duplicates.push(DuplicateImage {
+                path: path.to_path_buf(),
+                original,
+                kind,
+                is_kept: false,
+            });
+        }
+    }
+
+    fn show_duplicate_window(&mut self, ctx: &Context) {
+        let mut is_done = false;
+        egui::Window::new("duplicates")
+            .frame(
+                Frame::window(&ctx.style())
+                    .rounding(Rounding::same(10.))
+                    .inner_margin(Margin::same(10.)),
+            )
+            .fixed_size(vec2(550., 400.))
+            .title_bar(false)
+            .drag_bounds(ctx.screen_rect())
+            .show(ctx, |ui| {
+                ui.heading("Duplicate image");
+                if !self.duplicates.is_empty() {
+                    ui.label(
+                        "These images look like images already in the list. \
+                         Tick the ones to keep.",
+                    );
+                }
+                ui.add_space(10.);
+                ScrollArea::vertical()
+                    .max_height(300.)
+                    .auto_shrink([false; 2])
+                    .show(ui, |ui| {
+                        if !self.skipped_images.is_empty() {
+                            ui.label(format!(
+                                "Already in the list, so not added again:\n\n{}",
+                                self.skipped_images
+                                    .iter()
+                                    .map(|path| {
+                                        path.file_name().unwrap_or_default().to_string_lossy()
+                                    })
+                                    .collect::<Vec<Cow<str>>>()
+                                    .join("\n")
+                            ));
+                            ui.add_space(10.);
+                        }
+                        Grid::new("duplicates")
+                            .num_columns(4)
+                            .striped(true)
+                            .show(ui, |ui| {
+                                for duplicate in self.duplicates.iter_mut() {
+                                    let find = |path: &Path| {
+                                        self.images.iter().find(|image| image.path == path)
+                                    };
+                                    let (Some(image), Some(original)) =
+                                        (find(&duplicate.path), find(&duplicate.original))
+                                    else {
+                                        continue;
+                                    };
+                                    for image in [image, original] {
+                                        match image.current_texture(ctx) {
+                                            Some(texture_handle) => {
+                                                let size = image
+                                                    .calculate_image_dimension(DUPLICATE_THUMBNAIL_AREA);
+                                                ui.image(texture_handle, size)
+                                                .on_hover_text(image.path.to_string_lossy());
+                                            }
+                                            None => {
+                                                ui.spinner();
+                                            }
+                                        }
+                                    }
+                                    ui.label(format!(
+                                        "{}\n{} {}",
+                                        image.filename,
+                                        match duplicate.kind {
+                                            DuplicateKind::Identical => "is identical to",
+                                            DuplicateKind::Similar => "looks like",
+                                        },
+                                        original.filename
+                                    ));
+                                    ui.checkbox(&mut duplicate.is_kept, "Keep");
+                                    ui.end_row();
+                                }
+                            });
+                    });
+                ui.with_layout(Layout::right_to_left(Align::Center), |ui| {
+                    if ui
+                        .add_sized(
+                            vec2(100., 20.),
+                            widgets::Button::new("Done").rounding(Rounding::same(10.)),
+                        )
+                        .clicked()
+                    {
+                        is_done = true;
+                    }
+                });
+            });
+
+        if is_done {
+            self.skipped_images.clear();
+            let dropped: Vec<PathBuf> = self
+                .duplicates
+                .drain(..)
+                .filter(|duplicate| !duplicate.is_kept)
+                .map(|duplicate| duplicate.path)
+                .collect();
+            info!("Dropped {} duplicate images", dropped.len());
+            if !dropped.is_empty() {
+                self.images.retain(|image| !dropped.contains(&image.path));
+                self.select_images([], ctx);
+            }
+        }
+    }
+
+    fn select_images(&mut self, indices: impl IntoIterator<Item = usize>, ctx: &Context) {
+        let mut selection = Selection::default();
+        selection.set(indices);
+        self.selected_images = selection.rows().iter().copied().collect();
+        selection.store(ctx, Id::new("image_table"));
+    }
+
+    /// Move the images at `indices` to before the one at `to`, keeping them selected; the table
+    /// is no longer sorted afterwards
+    fn move_images(&mut self, indices: &[usize], to: usize, ctx: &Context) {
+        let moved = move_rows(&mut self.images, indices, to);
+        self.select_images(moved, ctx);
+        clear_sort(ctx, Id::new("image_table"));
+    }
+
+    /// Sort the images by the filename, size or processed size column of the table
+    fn sort_images(&mut self, sort: Sort, ctx: &Context) {
+        match sort.column {
+            1 => self.images.sort_by(|a, b| a.filename.cmp(&b.filename)),
+            2 => self.images.sort_by_key(|image| image.size),
+            3 => self.images.sort_by_key(|image| image.processed.as_ref().map(|(_, size)| *size)),
+            _ => return,
+        }
+        if sort.direction == SortDirection::Descending {
+            self.images.reverse();
+        }
+        self.select_images([], ctx);
+    }
+
+    fn remove_images(&mut self, indices: &[usize], ctx: &Context) {
+        let mut index = 0;
+        self.images.retain(|_| {
+            index += 1;
+            !indices.contains(&(index - 1))
+        });
+        self.select_images([], ctx);
+    }
+
+    fn apply_viewer_action(&mut self, action: ViewerAction, ctx: &Context) {
+        let Some(viewer) = &mut self.image_viewer else {
+            return;
+        };
+        let index = viewer.index;
+        match action {
+            ViewerAction::Previous => viewer.index -= 1,
+            ViewerAction::Next => viewer.index += 1,
+            ViewerAction::MoveUp => {
+                viewer.index -= 1;
+                self.move_images(&[index], index - 1, ctx);
+            }
+            ViewerAction::MoveDown => {
+                viewer.index += 1;
+                self.move_images(&[index], index + 2, ctx);
+            }
+            ViewerAction::Remove => {
+                // The next image is shown in its place, or the previous one if it was the last
+                viewer.index = index.min(self.images.len().saturating_sub(2));
+                self.remove_images(&[index], ctx);
+                if self.images.is_empty() {
+                    self.image_viewer = None;
+                }
+            }
+            ViewerAction::Close => self.image_viewer = None,
+        }
+    }
+
+    /// Scan the content for private metadata in the background before creating the torrent
+    fn start_upload(&mut self, ctx: &Context) {
+        info!("Begin torrent upload");
+        self.dialog_channel
+            .0
+            .send(DialogMessage(
+                Cow::Borrowed("Scanning the content for private metadata..."),
+                false,
+            ))
+            .unwrap();
+
+        let content_path = self.content.clone().unwrap().0;
+        let sender = self.privacy_channel.0.clone();
+        let ctx = ctx.clone();
+        std::thread::spawn(move || {
+            // The receiver only disappears when the application is closing
+            let _ = sender.send(privacy::scan(content_path));
+            ctx.request_repaint();
+        });
+    }
+
+    /// Create the torrent, or queue the upload if it was added to the queue
+    fn continue_upload(&mut self, ctx: &Context) {
+        if self.is_queueing {
+            self.queue_upload(ctx);
+        } else {
+            self.create_torrent(ctx);
+        }
+    }
+
+    /// Move the form into the upload queue and clear it for the next upload
+    fn queue_upload(&mut self, ctx: &Context) {
+        let job = UploadJob::new(
+            self.to_draft(),
+            self.images
+                .iter()
+                .map(|image| image.upload_path().to_path_buf())
+                .collect(),
+        );
+        let message = match self.upload_queue.push(job) {
+            Ok(()) => {
+                self.reset_form(ctx);
+                format!(
+                    "Added to the upload queue\n\n{} uploads pending",
+                    self.upload_queue.pending_count()
+                )
+            }
+            Err(err) => {
+                warn!(?err, "Unable to queue the upload");
+                format!("Unable to add to the upload queue:\n\n{err:#}")
+            }
+        };
+        self.dialog_channel
+            .0
+            .send(DialogMessage(Cow::Owned(message), true))
+            .unwrap();
+    }
+
+    /// Create the torrent in the background, recording the upload in the history once it is
+    /// created
+    fn create_torrent(&mut self, ctx: &Context) {
+        self.dialog_channel
+            .0
+            .send(DialogMessage(
+                Cow::Borrowed("Creating torrent...\n\nThis may take a while..."),
+                false,
+            ))
+            .unwrap();
+
+        let content_path = self.content.clone().unwrap().0;
+        let sender = self.dialog_channel.0.clone();
+        let draft = self.to_draft();
+        let history_sender = self.history_channel.0.clone();
+        let ctx = ctx.clone();
+        std::thread::spawn(move || {
+            if create_torrent_file(content_path, sender) {
+                // The receiver only disappears when the application is closing
+                let _ = history_sender.send(draft);
+                ctx.request_repaint();
+            }
+        });
+    }
+
+    /// Generate a contact sheet for every video in the content in the background; each one is added
+    /// to the images as soon as it is ready
+    fn generate_contact_sheets(&self, ctx: &Context) {
+        info!("Begin contact sheet generation");
+        self.dialog_channel
+            .0
+            .send(DialogMessage(
+                Cow::Borrowed("Generating contact sheets...\n\nThis may take a while..."),
+                false,
+            ))
+            .unwrap();
+
+        let content_path = self.content.clone().unwrap().0;
+        let dialog_sender = self.dialog_channel.0.clone();
+        let sender = self.contact_sheet_channel.0.clone();
+        let ctx = ctx.clone();
+        std::thread::spawn(move || {
+            let videos = list_videos(&content_path);
+            let mut errors = Vec::new();
+            let message = if let Err(err) = check_ffmpeg() {
+                warn!(?err, "Unable to run ffmpeg");
+                err.to_string()
+            } else if videos.is_empty() {
+                "No videos were found in the content".to_owned()
+            } else {
+                for video in videos.iter() {
+                    match generate_video_thumbnail_image(video, output_dir()) {
+                        Ok(path) => {
+                            // The receiver only disappears when the application is closing
+                            let _ = sender.send(path);
+                            ctx.request_repaint();
+                        }
+                        Err(err) => {
+                            warn!(?err, "Unable to generate the contact sheet");
+                            let filename = video.file_name().unwrap_or_default().to_string_lossy();
+                            errors.push(format!("{filename}: {err}"));
+                        }
+                    }
+                }
+                format!(
+                    "Generated {} of {} contact sheets{}",
+                    videos.len() - errors.len(),
+                    videos.len(),
+                    if errors.is_empty() {
+                        String::new()
+                    } else {
+                        format!("\n\n{}", errors.join("\n"))
+                    }
+                )
+            };
+            let _ = dialog_sender.send(DialogMessage(Cow::Owned(message), true));
+            ctx.request_repaint();
+        });
+    }
+
+    fn show_privacy_window(&mut self, ctx: &Context) {
+        let Some(findings) = &self.privacy_findings else {
+            return;
+        };
+        let root = self
+            .content
+            .as_ref()
+            .and_then(|(path, _, _)| path.parent())
+            .map(|path| path.to_path_buf())
+            .unwrap_or_default();
+        let mut is_closing = false;
+        let mut is_continuing = false;
+        egui::Window::new("privacy")
+            .frame(
+                Frame::window(&ctx.style())
+                    .rounding(Rounding::same(10.))
+                    .inner_margin(Margin::same(10.)),
+            )
+            .fixed_size(vec2(550., 400.))
+            .title_bar(false)
+            .drag_bounds(ctx.screen_rect())
+            .show(ctx, |ui| {
+                ui.heading("Private metadata found");
+                ui.label(
+                    "The following files contain metadata which may identify you. \
+                     Consider removing it before uploading.",
+                );
+                ui.add_space(10.);
+                ScrollArea::vertical()
+                    .max_height(300.)
+                    .auto_shrink([false; 2])
+                    .show(ui, |ui| {
+                        let mut previous_path = None;
+                        for finding in findings {
+                            if previous_path != Some(&finding.path) {
+                                previous_path = Some(&finding.path);
+                                ui.add_space(5.);
+                                ui.horizontal(|ui| {
+                                    if ui.small_button("Open").clicked() {
+                                        if let Err(err) = open::that(&finding.path) {
+                                            warn!(
+                                                ?err,
+                                                "Failed to open {}",
+                                                finding.path.to_string_lossy()
+                                            );
+                                        }
+                                    }
+                                    ui.strong(
+                                        finding
+                                            .path
+                                            .strip_prefix(&root)
+                                            .unwrap_or(&finding.path)
+                                            .to_string_lossy(),
+                                    );
+                                });
+                            }
+                            ui.colored_label(
+                                Severity::Warning.to_color(),
+                                format!(
+                                    "{} {}: {}",
+                                    Severity::Warning.to_symbol(),
+                                    finding.kind,
+                                    finding.detail
+                                ),
+                            );
+                        }
+                    });
+                ui.with_layout(Layout::right_to_left(Align::Center), |ui| {
+                    if ui
+                        .add_sized(
+                            vec2(100., 20.),
+                            widgets::Button::new("Continue").rounding(Rounding::same(10.)),
+                        )
+                        .clicked()
+                    {
+                        is_continuing = true;
+                    }
+                    if ui
+                        .add_sized(
+                            vec2(100., 20.),
+                            widgets::Button::new("Cancel").rounding(Rounding::same(10.)),
+                        )
+                        .clicked()
+                    {
+                        is_closing = true;
+                    }
+                });
+            });
+
+        if is_continuing {
+            info!("Continued uploading despite private metadata");
+            self.privacy_findings = None;
+            self.continue_upload(ctx);
+        } else if is_closing {
+            info!("Upload cancelled because of private metadata");
+            self.privacy_findings = None;
+        }
+    }
+
+    fn show_preview_window(&mut self, ctx: &Context) {
+        egui::Window::new("preview")
+            .frame(
+                Frame::window(&ctx.style())
+                    .rounding(Rounding::same(10.))
+                    .inner_margin(Margin::same(10.)),
+            )
+            .fixed_size(vec2(600., 500.))
+            .title_bar(false)
+            .drag_bounds(ctx.screen_rect())
+            .show(ctx, |ui| {
+                ScrollArea::vertical()
+                    .max_height(460.)
+                    .auto_shrink([false; 2])
+                    .show(ui, |ui| {
+                        Preview {
+                            title: &self.title,
+                            categories: &self.categories,
+                            tags: self
+                                .tags
+                                .iter()
+                                .chain(self.custom_tags.iter())
+                                .filter(|t| *t.1)
+                                .map(|t| t.0)
+                                .collect(),
+                            images: &self.images,
+                            image_area: self.config.image_area,
+                            description: &self.description,
+                            files: self.preview_files.as_deref(),
+                            media: &self.media_info,
+                        }
+                        .show(ui);
+                    });
+                ui.with_layout(Layout::right_to_left(Align::Center), |ui| {
+                    if ui
+                        .add_sized(
+                            vec2(100., 20.),
+                            widgets::Button::new("Close").rounding(Rounding::same(10.)),
+                        )
+                        .clicked()
+                    {
+                        self.is_preview_open = false;
+                    }
+                });
+            });
+    }
+
+    fn template_values(&self) -> TemplateValues {
+        let mut values = match &self.content {
+            Some((path, _, size)) => TemplateValues::from_content(path, *size),
+            None => TemplateValues::default(),
+        };
+        values.set_media(&self.media_info);
+        values.image_urls = self
+            .images
+            .iter()
+            .filter_map(|image| image.hosted_url.clone())
+            .collect();
+        values.categories = self
+            .categories
+            .iter()
+            .filter(|c| **c != Category::None)
+            .map(|c| c.to_string())
+            .collect();
+        values
+    }
+
+    fn apply_template(&mut self, template: &Template) {
+        info!("Applied template {}", template.name);
+        self.description = template.render(&self.template_values());
+    }
+
+    fn show_template_window(&mut self, ctx: &Context) {
+        egui::Window::new("templates")
+            .frame(
+                Frame::window(&ctx.style())
+                    .rounding(Rounding::same(10.))
+                    .inner_margin(Margin::same(10.)),
+            )
+            .fixed_size(vec2(500., 350.))
+            .title_bar(false)
+            .drag_bounds(ctx.screen_rect())
+            .show(ctx, |ui| {
+                ui.horizontal_wrapped(|ui| {
+                    for (index, template) in self.templates.iter().enumerate() {
+                        if ui
+                            .selectable_label(self.selected_template == Some(index), &template.name)
+                            .clicked()
+                        {
+                            self.selected_template = Some(index);
+                            self.template_buffer = template.clone();
+                        }
+                    }
+                    if ui.button("➕").on_hover_text("New template").clicked() {
+                        self.selected_template = None;
+                        self.template_buffer = Template::new("");
+                    }
+                });
+                ui.separator();
+
+                ui.add(
+                    widgets::TextEdit::singleline(&mut self.template_buffer.name)
+                        .desired_width(ui.available_width())
+                        .hint_text("Template name"),
+                );
+                ScrollArea::vertical()
+                    .max_height(200.)
+                    .show(ui, |ui| {
+                        ui.add_sized(
+                            vec2(ui.available_width(), 200.),
+                            widgets::TextEdit::multiline(&mut self.template_buffer.body),
+                        );
+                    });
+                ui.small(format!(
+                    "Placeholders: {}",
+                    PLACEHOLDERS.map(|p| format!("{{{p}}}")).join(" ")
+                ));
+
+                ui.with_layout(Layout::right_to_left(Align::Center), |ui| {
+                    if ui
+                        .add_sized(
+                            vec2(80., 20.),
+                            widgets::Button::new("Close").rounding(Rounding::same(10.)),
+                        )
+                        .clicked()
+                    {
+                        self.is_template_menu_open = false;
+                    }
+                    if ui
+                        .add_enabled(
+                            self.selected_template.is_some(),
+                            widgets::Button::new("Delete").rounding(Rounding::same(10.)),
+                        )
+                        .clicked()
+                    {
+                        let template = self.templates.remove(self.selected_template.unwrap());
+                        info!("Deleted template {}", template.name);
+                        self.selected_template = None;
+                        self.template_buffer = Template::new("");
+                        Template::save(config_local_dir("templates.json"), &self.templates);
+                    }
+                    if ui
+                        .add_enabled(
+                            !self.template_buffer.name.trim().is_empty(),
+                            widgets::Button::new("Save").rounding(Rounding::same(10.)),
+                        )
+                        .clicked()
+                    {
+                        match self.selected_template {
+                            Some(index) => self.templates[index] = self.template_buffer.clone(),
+                            None => {
+                                self.templates.push(self.template_buffer.clone());
+                                self.selected_template = Some(self.templates.len() - 1);
+                            }
+                        }
+                        info!("Saved template {}", self.template_buffer.name);
+                        Template::save(config_local_dir("templates.json"), &self.templates);
+                    }
+                });
+            });
+    }
+
+    fn to_draft(&self) -> Draft {
+        Draft {
+            is_file: self.is_file,
+            content: self.content.as_ref().map(|(path, _, _)| path.clone()),
+            categories: self
+                .categories
+                .iter()
+                .filter(|c| **c != Category::None)
+                .map(|c| c.to_string())
+                .collect(),
+            images: self.images.iter().map(|image| image.path.clone()).collect(),
+            image_urls: self
+                .images
+                .iter()
+                .filter_map(|image| Some((image.path.clone(), image.hosted_url.clone()?)))
+                .collect(),
+            title: self.title.clone(),
+            description: self.description.clone(),
+            tags: self
+                .tags
+                .iter()
+                .filter(|t| *t.1)
+                .map(|t| t.0.text.clone())
+                .collect(),
+            custom_tags: self
+                .custom_tags
+                .iter()
+                .filter(|t| *t.1)
+                .map(|t| t.0.clone())
+                .collect(),
+        }
+    }
+
+    /// Record the upload of `draft` once its torrent has been created
+    fn record_history(&mut self, draft: Draft) {
+        let Some(content) = draft.content else {
+            return;
+        };
+        let mut history = HistoryEntry::load(data_local_dir("history.json"));
+        history.push(HistoryEntry::new(
+            content,
+            draft.title,
+            draft.categories,
+            draft
+                .tags
+                .into_iter()
+                .chain(draft.custom_tags.into_iter().map(|tag| tag.text))
+                .collect(),
+        ));
+        HistoryEntry::save(data_local_dir("history.json"), &history);
+        self.category_usage = category_usage(&history);
+    }
+
+    /// Category drop-down of slot `number`, listing the favourites first and then the other
+    /// categories by usage; return whether the selection has changed
+    ///
+    /// Typing filters the list, Enter picks the first match and a secondary click on a
+    /// category pins or unpins it as a favourite.
+    fn show_category_combo(&mut self, ui: &mut Ui, number: usize) -> bool {
+        egui::ComboBox::new(number, "")
+            .selected_text(self.categories[number].to_string())
+            .width(250.)
+            .show_ui(ui, |ui| {
+                if self.category_query_slot != Some(number) {
+                    self.category_query.clear();
+                    self.category_query_slot = Some(number);
+                }
+                let search = ui.add(
+                    widgets::TextEdit::singleline(&mut self.category_query)
+                        .id(Id::new("category_query").with(number))
+                        .hint_text("Search categories"),
+                );
+                if ui.memory(|m| m.focus().is_none()) {
+                    search.request_focus();
+                }
+
+                let (favourites, others) = filter_categories(
+                    &self.category_query,
+                    &self.site_categories,
+                    &self.categories[..number],
+                    &self.config.favourite_categories,
+                    &self.category_usage,
+                );
+
+                // A singleline text edit gives up its focus when Enter is pressed
+                let mut selected = None;
+                if search.lost_focus() && ui.input(|i| i.key_pressed(Key::Enter)) {
+                    selected = top_match(&self.category_query, &favourites, &others);
+                    ui.memory_mut(|m| m.close_popup());
+                }
+
+                if self.category_query.is_empty()
+                    && ui
+                        .selectable_label(
+                            self.categories[number] == Category::None,
+                            Category::None.to_string(),
+                        )
+                        .clicked()
+                {
+                    selected = Some(Category::None);
+                }
+                let mut toggled_favourite = None;
+                for (title, categories) in [("Favourites", &favourites), ("Categories", &others)] {
+                    if categories.is_empty() {
+                        continue;
+                    }
+                    ui.separator();
+                    ui.small(title);
+                    for category in categories {
+                        let usage = self
+                            .category_usage
+                            .get(&category.to_string())
+                            .copied()
+                            .unwrap_or_default();
+                        let response = ui
+                            .selectable_label(
+                                self.categories[number] == *category,
+                                category.to_string(),
+                            )
+                            .on_hover_text(format!(
+                                "Used in {usage} upload(s); right-click to pin or unpin"
+                            ));
+                        if response.clicked() {
+                            selected = Some(*category);
+                        }
+                        if response.secondary_clicked() {
+                            toggled_favourite = Some(category.to_string());
+                        }
+                    }
+                }
+                let unsupported: Vec<&str> = self
+                    .site_categories
+                    .unsupported()
+                    .into_iter()
+                    .filter(|name| fuzzy_score(&self.category_query, name).is_some())
+                    .collect();
+                if !unsupported.is_empty() {
+                    ui.separator();
+                    ui.small("Not supported yet");
+                    for name in unsupported {
+                        ui.add_enabled(false, widgets::SelectableLabel::new(false, name))
+                            .on_disabled_hover_text(
+                                "New on the site; this version of QTM2 cannot select it yet",
+                            );
+                    }
+                }
+
+                if let Some(name) = toggled_favourite {
+                    if self.config.favourite_categories.contains(&name) {
+                        self.config.favourite_categories.retain(|favourite| *favourite != name);
+                    } else {
+                        self.config.favourite_categories.push(name);
+                    }
+                    self.config.save(config_local_dir("config.toml"));
+                }
+                match selected {
+                    Some(category) if category != self.categories[number] => {
+                        self.categories[number] = category;
+                        self.category_query_slot = None;
+                        true
+                    }
+                    _ => false,
+                }
+            })
+            .inner
+            .unwrap_or(false)
+    }
+
+    fn reset_form(&mut self, ctx: &Context) {
+        self.is_file = true;
+        self.set_content(None, ctx);
+        self.categories = [Category::None; 5];
+        self.images.clear();
+        self.select_images([], ctx);
+        self.title.clear();
+        self.description.clear();
+        self.template_to_apply = None;
+        self.tags.values_mut().for_each(|is_selected| *is_selected = false);
+        self.custom_tags
+            .values_mut()
+            .for_each(|is_selected| *is_selected = false);
+        self.dismissed_suggestions.clear();
+    }
+
+    fn restore_draft(&mut self, draft: Draft, ctx: &Context) {
+        self.reset_form(ctx);
+        let mut missing = Vec::new();
+
+        self.is_file = draft.is_file;
+        if let Some(path) = draft.content {
+            match content_from_path(path.clone()) {
+                Ok(content) => self.set_content(Some(content), ctx),
+                Err(err) => {
+                    warn!(?err, "Unable to restore the content of the draft");
+                    missing.push(format!("Content: {}", path.to_string_lossy()));
+                }
+            }
+        }
+
+        let mut number = 0;
+        for category in draft.categories.iter().take(self.categories.len()) {
+            match Category::from_str(category) {
+                Ok(category) if category != Category::None => {
+                    // Kept so that it is flagged in the form rather than silently dropped
+                    if !self.site_categories.offers(category) {
+                        missing.push(format!("Category (no longer exists on the site): {category}"));
+                    }
+                    self.categories[number] = category;
+                    number += 1;
+                }
+                _ => missing.push(format!("Category: {category}")),
+            }
+        }
+
+        for path in draft.images {
+            if path.is_file() {
+                let mut image = self.image_loader.load(&path, ctx);
+                image.hosted_url = draft.image_urls.get(&path).cloned();
+                self.images.push(image);
+            } else {
+                missing.push(format!("Image: {}", path.to_string_lossy()));
+            }
+        }
+
+        self.title = draft.title;
+        self.description = draft.description;
+
+        for text in draft.tags {
+            match self.tags.iter_mut().find(|t| t.0.text == text) {
+                Some((_, is_selected)) => *is_selected = true,
+                None => missing.push(format!("Tag: {text}")),
+            }
+        }
+        if !draft.custom_tags.is_empty() {
+            for tag in draft.custom_tags {
+                self.custom_tags.insert(tag, true);
+            }
+            self.save_custom_tags();
+        }
+
+        if !missing.is_empty() {
+            self.dialog_channel
+                .0
+                .send(DialogMessage(
+                    Cow::Owned(format!(
+                        "Some parts of the draft could not be restored:\n\n{}",
+                        missing.join("\n")
+                    )),
+                    true,
+                ))
+                .unwrap();
+        }
+    }
+
+    fn save_config_if_dirty(&mut self) {
+        if self.is_config_dirty {
+            self.config.save(config_local_dir("config.toml"));
+            self.is_config_dirty = false;
+        }
+    }
+
+    fn autosave(&mut self) {
+        self.last_autosave = Instant::now();
+        if !self.is_autosave_enabled {
+            return;
+        }
+        let draft = self.to_draft();
+        if draft == self.last_saved_draft {
+            return;
+        }
+        let path = draft_path(&self.draft_name);
+        if draft.is_empty() {
+            if path.exists() {
+                Draft::delete(path);
+            }
+        } else {
+            draft.save(path);
+        }
+        self.last_saved_draft = draft;
+    }
+
+    /// Move the current draft to `name`, replacing any draft already saved under it
+    fn save_draft_as(&mut self, name: String) {
+        if name != self.draft_name {
+            let previous_path = draft_path(&self.draft_name);
+            if previous_path.exists() {
+                Draft::delete(previous_path);
+            }
+        }
+        self.draft_name = name;
+        self.new_draft_name.clear();
+        self.last_saved_draft = Draft::default();
+        self.autosave();
+    }
+
+    fn show_queue_window(&mut self, ctx: &Context) {
+        egui::Window::new("queue")
+            .frame(
+                Frame::window(&ctx.style())
+                    .rounding(Rounding::same(10.))
+                    .inner_margin(Margin::same(10.)),
+            )
+            .fixed_size(vec2(550., 400.))
+            .title_bar(false)
+            .drag_bounds(ctx.screen_rect())
+            .show(ctx, |ui| {
+                ui.heading("Upload queue");
+                ui.label(
+                    "Jobs create the torrent and host the images; \
+                     submitting to the site and seeding are not implemented yet.",
+                );
+                ui.separator();
+
+                let mut action = None;
+                ScrollArea::vertical()
+                    .max_height(330.)
+                    .auto_shrink([false; 2])
+                    .show(ui, |ui| {
+                        if self.upload_queue.jobs.is_empty() {
+                            ui.label("Nothing is queued; use \"Add to queue\" to queue an upload.");
+                        }
+                        for job in self.upload_queue.jobs.iter() {
+                            ui.horizontal(|ui| {
+                                let content = job.draft.content.as_ref().and_then(|path| {
+                                    Some(path.file_name()?.to_string_lossy().into_owned())
+                                });
+                                ui.strong(match &job.draft.title {
+                                    title if !title.trim().is_empty() => title.clone(),
+                                    _ => content.unwrap_or_default(),
+                                });
+                                ui.with_layout(Layout::right_to_left(Align::Center), |ui| {
+                                    ui.set_enabled(!job.state.is_running());
+                                    if ui.button("✗").on_hover_text("Remove").clicked() {
+                                        action = Some((job.id, JobAction::Remove));
+                                    }
+                                    if job.state == JobState::Queued {
+                                        if ui.button("Skip").clicked() {
+                                            action = Some((job.id, JobAction::Skip));
+                                        }
+                                    } else if job.state.is_retryable() && ui.button("Retry").clicked() {
+                                        action = Some((job.id, JobAction::Retry));
+                                    }
+                                });
+                            });
+                            match &job.state {
+                                JobState::Hashing => {
+                                    ui.horizontal(|ui| {
+                                        ui.spinner();
+                                        ui.label(job.state.to_string());
+                                    });
+                                }
+                                JobState::Uploading { uploaded, total } => {
+                                    ui.add(
+                                        ProgressBar::new(*uploaded as f32 / *total as f32)
+                                            .text(job.state.to_string()),
+                                    );
+                                }
+                                JobState::Failed(_) => {
+                                    ui.colored_label(
+                                        Severity::Error.to_color(),
+                                        format!(
+                                            "{} {}",
+                                            Severity::Error.to_symbol(),
+                                            job.state
+                                        ),
+                                    );
+                                }
+                                JobState::ReadyToUpload => {
+                                    let torrent = job
+                                        .torrent
+                                        .as_ref()
+                                        .map(|path| path.to_string_lossy().into_owned())
+                                        .unwrap_or_default();
+                                    ui.label(format!("{}: {torrent}", job.state));
+                                }
+                                JobState::Queued | JobState::Skipped => {
+                                    ui.label(job.state.to_string());
+                                }
+                            }
+                            ui.separator();
+                        }
+                    });
+
+                match action {
+                    Some((id, JobAction::Retry)) => self.upload_queue.retry(id),
+                    Some((id, JobAction::Skip)) => self.upload_queue.skip(id),
+                    Some((id, JobAction::Remove)) => self.upload_queue.remove(id),
+                    None => {}
+                }
+
+                ui.with_layout(Layout::right_to_left(Align::Center), |ui| {
+                    if ui
+                        .add_sized(
+                            vec2(100., 20.),
+                            widgets::Button::new("Close").rounding(Rounding::same(10.)),
+                        )
+                        .clicked()
+                    {
+                        self.is_queue_open = false;
+                    }
+                });
+            });
+    }
+
+    fn show_draft_window(&mut self, ctx: &Context) {
+        egui::Window::new("drafts")
+            .frame(
+                Frame::window(&ctx.style())
+                    .rounding(Rounding::same(10.))
+                    .inner_margin(Margin::same(10.)),
+            )
+            .fixed_size(vec2(400., 250.))
+            .title_bar(false)
+            .drag_bounds(ctx.screen_rect())
+            .show(ctx, |ui| {
+                ui.set_enabled(self.dialog.is_none());
+                if self.is_restore_prompt {
+                    ui.label("Drafts from a previous session were found; restore one?");
+                } else {
+                    ui.label(format!("Current draft: {}", self.draft_name));
+                }
+                ui.separator();
+
+                let mut action = None;
+                ScrollArea::vertical()
+                    .max_height(150.)
+                    .auto_shrink([false; 2])
+                    .show(ui, |ui| {
+                        for name in Draft::list(cache_dir("drafts")) {
+                            ui.horizontal(|ui| {
+                                ui.monospace(&name);
+                                ui.with_layout(Layout::right_to_left(Align::Center), |ui| {
+                                    if ui
+                                        .button("✗")
+                                        .on_hover_text("Delete draft")
+                                        .clicked()
+                                    {
+                                        action = Some((name.clone(), false));
+                                    }
+                                    if ui.button("Load").clicked() {
+                                        action = Some((name.clone(), true));
+                                    }
+                                });
+                            });
+                        }
+                    });
+
+                match action {
+                    Some((name, true)) => {
+                        self.autosave();
+                        if let Some(draft) = Draft::load(draft_path(&name)) {
+                            self.restore_draft(draft, ctx);
+                            info!("Restored draft {name}");
+                            self.draft_name = name;
+                            self.last_saved_draft = self.to_draft();
+                            self.is_restore_prompt = false;
+                            self.is_draft_menu_open = false;
+                        }
+                    }
+                    Some((name, false)) => {
+                        Draft::delete(draft_path(&name));
+                        if name == self.draft_name {
+                            self.last_saved_draft = Draft::default();
+                        }
+                    }
+                    None => {}
+                }
 
-impl Qtm {
-    pub fn new(cc: &eframe::CreationContext<'_>, config: QtmConfig, tags: Vec<TagData>) -> Self {
-        info!("Started Main Application");
-        set_context(cc, config.theme);
+                ui.separator();
+                ui.horizontal(|ui| {
+                    ui.add(
+                        widgets::TextEdit::singleline(&mut self.new_draft_name)
+                            .desired_width(200.)
+                            .hint_text("Draft name"),
+                    );
+                    let name = Draft::sanitise_name(&self.new_draft_name);
+                    if ui
+                        .add_enabled(!name.is_empty(), widgets::Button::new("Save as"))
+                        .clicked()
+                    {
+                        if name != self.draft_name && draft_path(&name).exists() {
+                            self.draft_to_overwrite = Some(name);
+                        } else {
+                            self.save_draft_as(name);
+                        }
+                    }
+                });
+                if let Some(name) = self.draft_to_overwrite.clone() {
+                    ui.horizontal(|ui| {
+                        ui.colored_label(
+                            Severity::Warning.to_color(),
+                            format!("Overwrite the draft \"{name}\"?"),
+                        );
+                        if ui.button("Overwrite").clicked() {
+                            self.draft_to_overwrite = None;
+                            self.save_draft_as(name);
+                        }
+                        if ui.button("Cancel").clicked() {
+                            self.draft_to_overwrite = None;
+                        }
+                    });
+                }
 
-        Self {
-            config,
-            dialog: None,
-            dialog_channel: mpsc::channel(),
-            is_file: true,
-            content: None,
-            categories: [Category::None; 5],
-            images: Vec::new(),
-            selected_index: None,
-            title: "".to_owned(),
-            description: "".to_owned(),
-            tags: tags.into_iter().map(|tag| (tag, false)).collect(),
-            is_tag_menu_open: false,
-            new_custom_tag: TagData {
-                text: "".to_owned(),
-                color: TagColor::BlueGrey,
-            },
-            // TODO: Use `fetch_custom` to load custom tags from cache
-            custom_tags: BTreeMap::new(),
-        }
+                ui.with_layout(Layout::right_to_left(Align::Center), |ui| {
+                    if ui
+                        .add_sized(
+                            vec2(100., 20.),
+                            widgets::Button::new("Close").rounding(Rounding::same(10.)),
+                        )
+                        .clicked()
+                    {
+                        self.is_restore_prompt = false;
+                        self.is_draft_menu_open = false;
+                    }
+                    if ui
+                        .add_sized(
+                            vec2(100., 20.),
+                            widgets::Button::new("New draft").rounding(Rounding::same(10.)),
+                        )
+                        .clicked()
+                    {
+                        self.autosave();
+                        self.reset_form(ctx);
+                        self.draft_name = Draft::generate_name();
+                        self.last_saved_draft = Draft::default();
+                        self.is_restore_prompt = false;
+                        self.is_draft_menu_open = false;
+                    }
+                });
+            });
     }
 
     fn show_dialog_window(&mut self, context: &Context, message: &str, is_ok_showing: bool) {
@@ -100,17 +1847,182 @@ impl Qtm {
             });
     }
 
+    fn set_content(&mut self, content: Option<(PathBuf, String, u64)>, ctx: &Context) {
+        self.set_scanned_content(content.map(scan_content), ctx);
+    }
+
+    fn set_scanned_content(&mut self, scanned: Option<ScannedContent>, ctx: &Context) {
+        self.pending_content = None;
+        self.media_info.clear();
+        match scanned {
+            Some((content, issues, tokens)) => {
+                self.probe_content(content.0.clone(), ctx);
+                self.content_issues = issues;
+                self.content_tokens = tokens;
+                self.content = Some(content);
+            }
+            None => {
+                self.content_issues.clear();
+                self.content_tokens.clear();
+                self.content = None;
+            }
+        }
+    }
+
+    /// Measure and validate the content at `path` in the background before setting it
+    fn load_content(&mut self, path: PathBuf, ctx: &Context) {
+        info!("Set the content to {}", path.to_string_lossy());
+        self.set_content(None, ctx);
+        self.pending_content = Some(path.clone());
+
+        let sender = self.content_channel.0.clone();
+        let ctx = ctx.clone();
+        std::thread::spawn(move || {
+            let scanned = content_from_path(path.clone()).map(scan_content);
+            // The receiver only disappears when the application is closing
+            let _ = sender.send((path, scanned));
+            ctx.request_repaint();
+        });
+    }
+
+    /// Probe the videos in the content in the background
+    /// List the files of the content for the preview in the background
+    fn list_preview_files(&mut self, ctx: &Context) {
+        let Some((content_path, _, _)) = self.content.clone() else {
+            self.preview_files = Some(Vec::new());
+            return;
+        };
+        self.preview_files = None;
+        let sender = self.preview_files_channel.0.clone();
+        let ctx = ctx.clone();
+        std::thread::spawn(move || {
+            let files = list_files(&content_path);
+            // The receiver only disappears when the application is closing
+            let _ = sender.send((content_path, files));
+            ctx.request_repaint();
+        });
+    }
+
+    fn probe_content(&self, content_path: PathBuf, ctx: &Context) {
+        let sender = self.media_channel.0.clone();
+        let ctx = ctx.clone();
+        std::thread::spawn(move || {
+            let root = content_path.parent().unwrap_or(&content_path);
+            let media = list_videos(&content_path)
+                .into_par_iter()
+                .filter_map(|video| match probe(&video) {
+                    Ok(info) => Some((
+                        video
+                            .strip_prefix(root)
+                            .unwrap_or(&video)
+                            .to_string_lossy()
+                            .into_owned(),
+                        info,
+                    )),
+                    Err(err) => {
+                        warn!(?err, "Unable to probe {}", video.to_string_lossy());
+                        None
+                    }
+                })
+                .collect();
+            // The receiver only disappears when the application is closing
+            let _ = sender.send((content_path, media));
+            ctx.request_repaint();
+        });
+    }
+
+    /// Append the images among dropped paths to the images; a folder or other file becomes the
+    /// content instead
+    fn add_paths(&mut self, paths: Vec<PathBuf>, ctx: &Context) {
+        let (image_paths, mut other_paths): (Vec<PathBuf>, Vec<PathBuf>) = paths
+            .into_iter()
+            .partition(|path| path.is_file() && is_supported_image(path));
+        other_paths.retain(|path| path.exists());
+
+        if !image_paths.is_empty() {
+            let (images, skipped) =
+                load_new_images(&image_paths, &self.images, &self.image_loader, ctx);
+            info!("Added {} images", images.len());
+            self.add_images(images, skipped);
+        }
+
+        if let Some(content_path) = other_paths.first() {
+            self.is_file = content_path.is_file();
+            self.load_content(content_path.clone(), ctx);
+        }
+        if other_paths.len() > 1 {
+            self.dialog_channel
+                .0
+                .send(DialogMessage(
+                    Cow::Owned(format!(
+                        "Only one file or folder can be uploaded; ignored:\n\n{}",
+                        other_paths[1..]
+                            .iter()
+                            .map(|path| path.file_name().unwrap_or_default().to_string_lossy())
+                            .collect::<Vec<Cow<str>>>()
+                            .join("\n")
+                    )),
+                    true,
+                ))
+                .unwrap();
+        }
+    }
+
+    /// Add the image on the clipboard, or handle the copied files like dropped ones
+    fn paste(&mut self, ctx: &Context) {
+        match clipboard::paste(&self.paste_dir) {
+            Ok(Pasted::Image(path)) => {
+                let image = self.image_loader.load(path, ctx);
+                self.add_images(vec![image], Vec::new());
+            }
+            Ok(Pasted::Paths(paths)) => self.add_paths(paths, ctx),
+            Ok(Pasted::Nothing) => info!("Nothing to paste from the clipboard"),
+            Err(err) => {
+                warn!(?err, "Unable to paste from the clipboard");
+                self.dialog_channel
+                    .0
+                    .send(DialogMessage(
+                        Cow::Owned(format!("Unable to paste from the clipboard:\n\n{err}")),
+                        true,
+                    ))
+                    .unwrap();
+            }
+        }
+    }
+
+    /// Upload the images which are not hosted yet, or all of them again if `is_rehosting`
+    fn host_images(&mut self, is_rehosting: bool, ctx: &Context) {
+        match MultipartHost::new(self.config.image_host.clone()) {
+            Ok(host) => {
+                info!("Begin uploading images to {}", self.config.image_host.endpoint);
+                self.image_uploader
+                    .upload(&mut self.images, Arc::new(host), is_rehosting, ctx);
+            }
+            Err(err) => {
+                warn!(?err, "Unable to set up the image host");
+                self.dialog_channel
+                    .0
+                    .send(DialogMessage(
+                        Cow::Owned(format!("Unable to set up the image host:\n\n{err}")),
+                        true,
+                    ))
+                    .unwrap();
+            }
+        }
+    }
+
     fn is_acceptable(&self) -> bool {
-        // TODO: Reject if the content's name contains illegal characters
-        if self.content.is_none() {
+        // rejects if the content's name or any file within it is illegal
+        if self.content.is_none() || has_errors(&self.content_issues) {
             return false;
         }
-        // rejects if there is no category, image, title or description
-        // TODO: Add check for description only whitespace or newline character
+        // rejects if there is no category or image, or the title or description has errors;
+        // warnings are shown but do not block uploading, and the categories' form values are only
+        // needed when submitting to the site
         if self.categories[0] == Category::None
             || self.images.is_empty()
-            || self.title.trim().is_empty()
-            || self.description.trim().is_empty()
+            || has_errors(&validate_title(&self.title))
+            || has_errors(&validate_description(&self.description))
         {
             return false;
         }
@@ -118,8 +2030,138 @@ impl Qtm {
     }
 }
 
+fn scan_content(content: (PathBuf, String, u64)) -> ScannedContent {
+    let issues = validate_content(&content.0);
+    let tokens = tokenise_content(&content.0);
+    (content, issues, tokens)
+}
+
+fn show_issues(ui: &mut Ui, issues: &[Issue]) {
+    for issue in issues {
+        ui.colored_label(
+            issue.severity.to_color(),
+            format!("{} {}", issue.severity.to_symbol(), issue.message),
+        )
+        .on_hover_text(issue.severity.to_string());
+    }
+}
+
+fn show_drop_overlay(ctx: &Context, text: &str) {
+    let painter = ctx.layer_painter(LayerId::new(Order::Foreground, Id::new("drop_overlay")));
+    let screen_rect = ctx.screen_rect();
+    painter.rect_filled(screen_rect, 0., Color32::from_black_alpha(192));
+    painter.text(
+        screen_rect.center(),
+        Align2::CENTER_CENTER,
+        text,
+        TextStyle::Heading.resolve(&ctx.style()),
+        Color32::WHITE,
+    );
+}
+
 impl eframe::App for Qtm {
     fn update(&mut self, ctx: &Context, frame: &mut eframe::Frame) {
+        for path in self.contact_sheet_channel.1.try_iter() {
+            // Regenerated contact sheets replace the previous ones
+            self.images.retain(|image| image.path != path);
+            self.images.push(self.image_loader.load(path, ctx));
+        }
+        for path in self.image_loader.poll(&mut self.images, ctx) {
+            self.check_duplicate(&path);
+        }
+        while let Ok((path, scanned)) = self.content_channel.1.try_recv() {
+            // Content which has been replaced in the meantime is dropped
+            if self.pending_content.as_ref() != Some(&path) {
+                continue;
+            }
+            match scanned {
+                Ok(scanned) => self.set_scanned_content(Some(scanned), ctx),
+                Err(err) => {
+                    warn!(?err, "Unable to read the content at {}", path.to_string_lossy());
+                    self.pending_content = None;
+                    self.dialog_channel
+                        .0
+                        .send(DialogMessage(
+                            Cow::Owned(format!(
+                                "Unable to read the content at {}:\n\n{err}",
+                                path.to_string_lossy()
+                            )),
+                            true,
+                        ))
+                        .unwrap();
+                }
+            }
+        }
+        for (path, files) in self.preview_files_channel.1.try_iter() {
+            // Results for previously selected content are stale
+            if self.content.as_ref().is_some_and(|(content_path, _, _)| *content_path == path) {
+                self.preview_files = Some(files);
+            }
+        }
+        for (path, media) in self.media_channel.1.try_iter() {
+            // Results for previously selected content are stale
+            if self.content.as_ref().is_some_and(|(content_path, _, _)| *content_path == path) {
+                self.media_info = media;
+            }
+        }
+        for job in self.upload_queue.poll(&self.config.image_host, ctx) {
+            self.record_history(job.draft);
+        }
+        while let Ok(draft) = self.history_channel.1.try_recv() {
+            self.record_history(draft);
+        }
+        for synced in self.sync_channel.1.try_iter() {
+            match synced {
+                Synced::Tags(tags) => {
+                    info!("Applied the synced tags");
+                    // Selected tags stay selected if they are still listed
+                    let selected: Vec<String> = self
+                        .tags
+                        .iter()
+                        .filter(|(_, is_selected)| **is_selected)
+                        .map(|(tag, _)| tag.text.clone())
+                        .collect();
+                    self.tags = tags
+                        .into_iter()
+                        .map(|tag| {
+                            let is_selected = selected.contains(&tag.text);
+                            (tag, is_selected)
+                        })
+                        .collect();
+                }
+                Synced::Categories(categories) => {
+                    info!("Applied the synced categories");
+                    self.site_categories = categories;
+                }
+            }
+        }
+        let errors = self.image_uploader.poll(&mut self.images);
+        if !errors.is_empty() {
+            self.dialog_channel
+                .0
+                .send(DialogMessage(
+                    Cow::Owned(format!(
+                        "Some images could not be uploaded:\n\n{}",
+                        errors.join("\n")
+                    )),
+                    true,
+                ))
+                .unwrap();
+        }
+        let errors = self.preprocessor.poll(&mut self.images);
+        if !errors.is_empty() {
+            self.dialog_channel
+                .0
+                .send(DialogMessage(
+                    Cow::Owned(format!(
+                        "Some images could not be processed:\n\n{}",
+                        errors.join("\n")
+                    )),
+                    true,
+                ))
+                .unwrap();
+        }
+
         if self.dialog.is_none() || !self.dialog.as_ref().unwrap().1 {
             match self.dialog_channel.1.try_recv() {
                 Ok(message) => self.dialog = Some(message),
@@ -130,14 +2172,102 @@ impl eframe::App for Qtm {
             }
         }
 
+        // Polled after the dialog so that the scanning message is received before being replaced
+        if let Ok(findings) = self.privacy_channel.1.try_recv() {
+            if findings.is_empty() {
+                self.continue_upload(ctx);
+            } else {
+                info!("Found {} pieces of private metadata", findings.len());
+                self.dialog = None;
+                self.privacy_findings = Some(findings);
+            }
+        }
+        if self.privacy_findings.is_some() {
+            self.show_privacy_window(ctx);
+        }
+
         if let Some(DialogMessage(message, is_ok_showing)) = &self.dialog {
             self.show_dialog_window(ctx, &message.clone(), *is_ok_showing);
         }
 
+        if self.is_draft_menu_open {
+            self.show_draft_window(ctx);
+        }
+
+        if self.is_queue_open {
+            self.show_queue_window(ctx);
+        }
+
+        if self.is_template_menu_open {
+            self.show_template_window(ctx);
+        }
+
+        if self.is_preview_open {
+            self.show_preview_window(ctx);
+        }
+
+        if !self.duplicates.is_empty() || !self.skipped_images.is_empty() {
+            self.show_duplicate_window(ctx);
+        }
+
+        if let Some(viewer) = &mut self.image_viewer {
+            match viewer.show(ctx, &self.images) {
+                Some(action) => self.apply_viewer_action(action, ctx),
+                // The images may have been cleared in the meantime, e.g. by restoring a draft
+                None if viewer.index >= self.images.len() => self.image_viewer = None,
+                None => {}
+            }
+        }
+
+        let dropped_paths: Vec<PathBuf> = ctx.input(|i| {
+            i.raw
+                .dropped_files
+                .iter()
+                .filter_map(|file| file.path.clone())
+                .collect()
+        });
+        if !self.is_main_ui_enabled() && ctx.input(|i| !i.raw.hovered_files.is_empty()) {
+            show_drop_overlay(ctx, "Close the open window before dropping files");
+        }
+        if !dropped_paths.is_empty() && !self.is_main_ui_enabled() {
+            info!("Ignored {} paths dropped onto an open window", dropped_paths.len());
+            // A message in progress is not replaced; the overlay has already explained it
+            if self.dialog.is_none() {
+                self.dialog_channel
+                    .0
+                    .send(DialogMessage(
+                        Cow::Owned(format!(
+                            "Close the open window before dropping files; ignored:\n\n{}",
+                            dropped_paths
+                                .iter()
+                                .map(|path| path.file_name().unwrap_or_default().to_string_lossy())
+                                .collect::<Vec<Cow<str>>>()
+                                .join("\n")
+                        )),
+                        true,
+                    ))
+                    .unwrap();
+            }
+        }
+        if self.is_main_ui_enabled() {
+            if !dropped_paths.is_empty() {
+                self.add_paths(dropped_paths, ctx);
+            }
+            if ctx.input(|i| !i.raw.hovered_files.is_empty()) {
+                show_drop_overlay(ctx, "Drop the content or images here");
+            }
+            // Text fields handle pasting themselves
+            if ctx.memory(|m| m.focus().is_none_or(|id| id == Id::new("image_table")))
+                && ctx.input_mut(|i| i.consume_key(Modifiers::COMMAND, Key::V))
+            {
+                self.paste(ctx);
+            }
+        }
+
         egui::TopBottomPanel::top("top_panel")
             .exact_height(25.)
             .show(ctx, |ui| {
-                ui.set_enabled(self.dialog.is_none() && !self.is_tag_menu_open);
+                ui.set_enabled(self.is_main_ui_enabled());
                 ui.with_layout(Layout::left_to_right(Align::Center), |ui| {
                     if ui
                         .add_sized(
@@ -166,7 +2296,7 @@ impl eframe::App for Qtm {
                         .on_hover_text("Set default directory")
                         .clicked()
                     {
-                        if let Some((path, _, _)) =
+                        if let Some(path) =
                             select_content(false, self.config.default_directory.as_deref())
                         {
                             info!("Default directory set to {}", path.to_string_lossy());
@@ -174,6 +2304,31 @@ impl eframe::App for Qtm {
                             self.config.save(config_local_dir("config.toml"));
                         }
                     }
+
+                    if ui
+                        .add_sized(
+                            vec2(ui.available_height(), ui.available_height()),
+                            widgets::Button::new("📝"),
+                        )
+                        .on_hover_text("Drafts")
+                        .clicked()
+                    {
+                        self.is_draft_menu_open = true;
+                    }
+
+                    if ui
+                        .add_sized(
+                            vec2(ui.available_height(), ui.available_height()),
+                            widgets::Button::new("☰"),
+                        )
+                        .on_hover_text(format!(
+                            "Upload queue ({} pending)",
+                            self.upload_queue.pending_count()
+                        ))
+                        .clicked()
+                    {
+                        self.is_queue_open = true;
+                    }
                     ui.add_space(110.);
                     if ui
                         .add_sized(
@@ -187,6 +2342,7 @@ impl eframe::App for Qtm {
                             fs::remove_dir_all(dir).unwrap();
                         }
                         initialise_dirs().unwrap();
+                        self.is_autosave_enabled = false;
                         frame.close();
                     }
                 });
@@ -197,32 +2353,37 @@ impl eframe::App for Qtm {
             .show(ctx, |ui| {
                 ui.with_layout(Layout::right_to_left(Align::Center), |ui| {
                     ui.add_enabled_ui(
-                        self.dialog.is_none() && !self.is_tag_menu_open && self.is_acceptable(),
+                        self.is_main_ui_enabled() && self.is_acceptable(),
                         |ui| {
                             if ui
                                 .add_sized(vec2(150., 20.), widgets::Button::new("Upload torrent"))
                                 .clicked()
                             {
-                                info!("Begin torrent upload");
-                                self.dialog_channel
-                                    .0
-                                    .send(DialogMessage(
-                                        Cow::Borrowed(
-                                            "Creating torrent...\n\nThis may take a while...",
-                                        ),
-                                        false,
-                                    ))
-                                    .unwrap();
-
-                                let content_path = self.content.clone().unwrap().0;
-                                let sender = self.dialog_channel.0.clone();
-                                std::thread::spawn(|| {
-                                    create_torrent_file(content_path, sender);
-                                });
+                                self.is_queueing = false;
+                                self.start_upload(ctx);
+                            }
+                            if ui
+                                .add_sized(vec2(100., 20.), widgets::Button::new("Add to queue"))
+                                .on_hover_text("Upload in the background and clear the form")
+                                .clicked()
+                            {
+                                self.is_queueing = true;
+                                self.start_upload(ctx);
                             }
                         },
                     );
 
+                    if ui
+                        .add_enabled(
+                            self.is_main_ui_enabled(),
+                            widgets::Button::new("Preview").min_size(vec2(100., 20.)),
+                        )
+                        .clicked()
+                    {
+                        self.list_preview_files(ctx);
+                        self.is_preview_open = true;
+                    }
+
                     // Uploading rules
                     ui.add_space(10.);
                     let rule_url = "https://www.gaytor.rent/rules.php#102";
@@ -240,21 +2401,21 @@ impl eframe::App for Qtm {
                 ScrollArea::vertical()
                     .auto_shrink([false; 2])
                     .show(ui, |ui| {
-                        ui.set_enabled(self.dialog.is_none() && !self.is_tag_menu_open);
+                        ui.set_enabled(self.is_main_ui_enabled());
                         // Content type
                         ui.horizontal(|ui| {
                             if ui
                                 .radio_value(&mut self.is_file, true, "Upload File")
                                 .changed()
                             {
-                                self.content = None;
+                                self.set_content(None, ui.ctx());
                             }
                             ui.add_space(50.);
                             if ui
                                 .radio_value(&mut self.is_file, false, "Upload Folder")
                                 .changed()
                             {
-                                self.content = None;
+                                self.set_content(None, ui.ctx());
                             }
                         });
 
@@ -274,29 +2435,39 @@ impl eframe::App for Qtm {
                                             .add(egui::Button::new("...").min_size(vec2(40., 10.)))
                                             .clicked()
                                         {
-                                            self.content = select_content(
+                                            match select_content(
                                                 self.is_file,
                                                 self.config.default_directory.as_deref(),
-                                            );
+                                            ) {
+                                                Some(path) => self.load_content(path, ui.ctx()),
+                                                None => self.set_content(None, ui.ctx()),
+                                            }
                                         }
                                     });
                                 });
-                                ui.horizontal(|ui| {
-                                    if let Some((_, path_str, size)) = &self.content {
-                                        ui.add(
-                                            egui::TextEdit::singleline(&mut path_str.as_str())
-                                                .desired_width(ui.available_size().x - 120.)
-                                                .font(TextStyle::Monospace),
-                                        );
-                                        ui.add(
-                                            egui::TextEdit::singleline(
-                                                &mut ByteSize(*size).to_string().as_str(),
-                                            )
-                                                .desired_width(120.)
-                                                .horizontal_align(Align::Max)
-                                                .font(TextStyle::Monospace),
-                                        );
-                                    }
+                                ui.vertical(|ui| {
+                                    ui.horizontal(|ui| {
+                                        if let Some(path) = &self.pending_content {
+                                            ui.spinner();
+                                            ui.monospace(path.to_string_lossy());
+                                        }
+                                        if let Some((_, path_str, size)) = &self.content {
+                                            ui.add(
+                                                egui::TextEdit::singleline(&mut path_str.as_str())
+                                                    .desired_width(ui.available_size().x - 120.)
+                                                    .font(TextStyle::Monospace),
+                                            );
+                                            ui.add(
+                                                egui::TextEdit::singleline(
+                                                    &mut ByteSize(*size).to_string().as_str(),
+                                                )
+                                                    .desired_width(120.)
+                                                    .horizontal_align(Align::Max)
+                                                    .font(TextStyle::Monospace),
+                                            );
+                                        }
+                                    });
+                                    show_issues(ui, &self.content_issues);
                                 });
                                 ui.end_row();
 
@@ -311,41 +2482,24 @@ impl eframe::App for Qtm {
                                     ui.add_enabled_ui(
                                         number == 0 || self.categories[number - 1] != Category::None,
                                         |ui| {
-                                            let changed = egui::ComboBox::new(number, "")
-                                                .selected_text(format!("{}", self.categories[number]))
-                                                .width(250.)
-                                                .show_ui(ui, |ui| {
-                                                    let mut clicked = false;
-                                                    let (previous_categories, [current_category, ..]) = self.categories.split_at_mut(number) else {
-                                                        panic!("impossible");
-                                                    };
-                                                    for category in Category::iter()
-                                                        .filter(|c| *c == Category::None || !previous_categories.contains(c))
-                                                    {
-                                                        clicked |= ui
-                                                            .selectable_value(
-                                                                current_category,
-                                                                category,
-                                                                category.to_string(),
-                                                            )
-                                                            .clicked();
+                                            ui.vertical(|ui| {
+                                                let changed = self.show_category_combo(ui, number);
+                                                if changed {
+                                                    for category in self.categories[number + 1..].iter_mut() {
+                                                        *category = Category::None;
                                                     }
-                                                    clicked
-                                                })
-                                                .inner
-                                                .unwrap_or(false);
-                                            if changed {
-                                                for category in self.categories[number + 1..].iter_mut() {
-                                                    *category = Category::None;
                                                 }
-                                            }
+                                                // The unavailable list is only reported once, under the first category
+                                                if number == 0 || self.site_categories.is_available() {
+                                                    show_issues(ui, &validate_categories(&self.categories[number..=number], &self.site_categories));
+                                                }
+                                            });
                                         },
                                     );
                                     ui.end_row();
                                 }
 
                                 // Images
-                                // TODO: Add built-in video thumbnail generator
 
                                 ui.vertical(|ui| {
                                     ui.horizontal(|ui| {
@@ -355,32 +2509,83 @@ impl eframe::App for Qtm {
                                                 .add(egui::Button::new("...").min_size(vec2(40., 10.)))
                                                 .clicked()
                                             {
-                                                if let Some(mut images) = file_dialog::select_images(
+                                                if let Some((images, skipped)) = file_dialog::select_images(
                                                     self.config.default_directory.as_deref(),
                                                     &self.images,
-                                                    &self.dialog_channel.0,
+                                                    &self.image_loader,
                                                     ui,
                                                 ) {
-                                                    self.images.append(&mut images);
+                                                    self.add_images(images, skipped);
+                                                }
+                                            }
+                                            if ui
+                                                .add_enabled(
+                                                    self.content.is_some(),
+                                                    egui::Button::new("Contact sheets"),
+                                                )
+                                                .on_hover_text("Generate a contact sheet for every video in the content; requires ffmpeg")
+                                                .clicked()
+                                            {
+                                                self.generate_contact_sheets(ui.ctx());
+                                            }
+                                            if ui
+                                                .add_enabled(
+                                                    !self.images.is_empty(),
+                                                    egui::Button::new("Optimise"),
+                                                )
+                                                .on_hover_text("Resize, recompress and strip metadata within the limits of the site")
+                                                .clicked()
+                                            {
+                                                for image in self.images.iter_mut() {
+                                                    self.preprocessor.process(
+                                                        image,
+                                                        &self.config.site_profile,
+                                                        self.config.keep_image_metadata,
+                                                        ui.ctx(),
+                                                    );
                                                 }
                                             }
+                                            if ui
+                                                .checkbox(&mut self.config.keep_image_metadata, "Keep metadata")
+                                                .changed()
+                                            {
+                                                self.config.save(config_local_dir("config.toml"));
+                                            }
+                                            let is_host_configured = !self.config.image_host.endpoint.is_empty();
+                                            let response = ui
+                                                .add_enabled(
+                                                    is_host_configured && !self.images.is_empty(),
+                                                    egui::Button::new("Host"),
+                                                )
+                                                .on_hover_text(if is_host_configured {
+                                                    "Upload the images which are not hosted yet to the image host; \
+                                                     right-click to upload all of them again"
+                                                } else {
+                                                    "Configure an image host in config.toml first"
+                                                });
+                                            if response.clicked() {
+                                                self.host_images(false, ui.ctx());
+                                            }
+                                            response.context_menu(|ui| {
+                                                if ui.button("Host all again").clicked() {
+                                                    self.host_images(true, ui.ctx());
+                                                    ui.close_menu();
+                                                }
+                                            });
                                         });
                                     });
-                                    if let Some(selected_index) = self.selected_index {
+                                    if let (Some(&first), Some(&last)) = (self.selected_images.first(), self.selected_images.last()) {
                                         ui.with_layout(Layout::top_down(Align::Center), |ui| {
                                             ui.add_space(10.);
-                                            if ui.add(egui::Button::new("↑").min_size(vec2(50., 10.))).clicked() && selected_index != 0 {
-                                                self.images.swap(selected_index, selected_index - 1);
-                                                self.selected_index = Some(selected_index - 1);
+                                            let selected_images = self.selected_images.clone();
+                                            if ui.add(egui::Button::new("↑").min_size(vec2(50., 10.))).clicked() && first != 0 {
+                                                self.move_images(&selected_images, first - 1, ui.ctx());
                                             }
-                                            if ui.add(egui::Button::new("↓").min_size(vec2(50., 10.))).clicked() && selected_index != self.images.len() - 1 {
-                                                self.images.swap(selected_index, selected_index + 1);
-                                                self.selected_index = Some(selected_index + 1);
+                                            if ui.add(egui::Button::new("↓").min_size(vec2(50., 10.))).clicked() && last != self.images.len() - 1 {
+                                                self.move_images(&selected_images, last + 2, ui.ctx());
                                             }
                                             if ui.add(egui::Button::new("✗").min_size(vec2(50., 10.))).clicked() {
-                                                self.images.remove(selected_index);
-                                                self.selected_index = None;
-                                                ui.data_mut(|d| d.insert_persisted::<Option<usize>>(Id::new("selected_index"), None));
+                                                self.remove_images(&selected_images, ui.ctx());
                                             }
                                         });
                                     }
@@ -389,82 +2594,178 @@ impl eframe::App for Qtm {
                                 let (rect, _) = ui.allocate_exact_size(vec2(ui.available_size_before_wrap().x, 200.), selectable_table::SENSE_NONE);
                                 {
                                     let ui = &mut ui.child_ui(rect, *ui.layout());
+                                    let uploading = self.images.iter().filter(|image| image.is_uploading).count();
+                                    if uploading > 0 {
+                                        let hosted = self.images.iter().filter(|image| image.hosted_url.is_some()).count();
+                                        ui.add(
+                                            ProgressBar::new(hosted as f32 / (hosted + uploading) as f32)
+                                                .text(format!("Uploading {hosted}/{}", hosted + uploading)),
+                                        );
+                                    }
                                     let table = TableBuilder::new(ui)
                                         .striped(true)
                                         .vscroll(true)
                                         .layout(Layout::left_to_right(Align::Center))
-                                        .column(Column::fixed(50.))
+                                        .column(Column::auto())
                                         .column(Column::remainder())
-                                        .column(Column::fixed(100.))
-                                        .id(Id::new("selected_index"))
+                                        .column(Column::fixed(100.).resizable(true))
+                                        .column(Column::fixed(100.).resizable(true))
+                                        .column(Column::fixed(60.))
+                                        .id(Id::new("image_table"))
                                         .build();
 
-                                    self.selected_index = table
+                                    let response = table
                                         .header(20., |mut row| {
                                             row.col(|ui| {
                                                 ui.strong("Index");
                                             });
+                                            row.sort_col("Filename");
+                                            row.sort_col("Size");
+                                            row.sort_col("Processed");
                                             row.col(|ui| {
-                                                ui.strong("Filename");
-                                            });
-                                            row.col(|ui| {
-                                                ui.strong("Size");
+                                                ui.strong("Hosted");
                                             });
                                         })
                                         .body(|mut body| {
-                                            for (index, image) in self.images.iter().enumerate() {
-                                                let response = body.row(20., |mut row| {
-                                                    row.col(|ui| {
-                                                        ui.monospace(index.to_string());
-                                                    });
-                                                    row.col(|ui| {
+                                            let responses = body.rows(20., self.images.len(), |index, mut row| {
+                                                let image = &self.images[index];
+                                                row.col(|ui| {
+                                                    ui.monospace(index.to_string());
+                                                });
+                                                row.col(|ui| {
+                                                    ui.horizontal(|ui| {
+                                                        if image.is_loading {
+                                                            ui.spinner();
+                                                        }
                                                         ui.monospace(&image.filename);
                                                     });
-                                                    row.col(|ui| {
-                                                        ui.monospace(ByteSize(image.size).to_string());
-                                                    });
                                                 });
-                                                if response.clicked() {
-                                                    self.selected_index = Some(index);
+                                                row.col(|ui| {
+                                                    ui.monospace(ByteSize(image.size).to_string());
+                                                });
+                                                row.col(|ui| {
+                                                    match &image.processed {
+                                                        _ if image.is_processing => {
+                                                            ui.spinner();
+                                                        }
+                                                        Some((path, size)) => {
+                                                            ui.monospace(ByteSize(*size).to_string())
+                                                                .on_hover_text(path.to_string_lossy());
+                                                        }
+                                                        None => {
+                                                            ui.monospace("-");
+                                                        }
+                                                    }
+                                                });
+                                                row.col(|ui| {
+                                                    match &image.hosted_url {
+                                                        _ if image.is_uploading => {
+                                                            ui.spinner();
+                                                        }
+                                                        Some(url) => {
+                                                            ui.monospace("✔").on_hover_text(url);
+                                                        }
+                                                        None => {
+                                                            ui.monospace("-");
+                                                        }
+                                                    }
+                                                });
+                                            });
+                                            for (index, response) in responses {
+                                                let image = &self.images[index];
+                                                if response.double_clicked() {
+                                                    self.image_viewer = Some(ImageViewer::new(index));
                                                 }
                                                 if response.hovered() && image.texture_handle.is_some() {
                                                     show_tooltip(ui.ctx(), Id::new("image preview"), |ui| {
-                                                        ui.image(image.texture_handle.as_ref().unwrap(),
+                                                        ui.image(image.current_texture(ui.ctx()).unwrap(),
                                                                  image.calculate_image_dimension(self.config.image_area),
                                                         )
                                                     });
                                                 }
                                             }
-                                            self.selected_index
                                         });
+                                    self.selected_images = response.selection.rows().iter().copied().collect();
+                                    for event in response.events {
+                                        match event {
+                                            TableEvent::Moved { rows, to } => {
+                                                self.move_images(&rows, to, ui.ctx());
+                                            }
+                                            TableEvent::Deleted(rows) => self.remove_images(&rows, ui.ctx()),
+                                            TableEvent::Sorted(sort) => self.sort_images(sort, ui.ctx()),
+                                        }
+                                    }
                                 }
                                 ui.end_row();
 
                                 // Title and description
-                                // TODO: (1) Add invalid character regex and warning
-                                // TODO: (2) Add hyperlinks to Gaytor.rent official guides
+                                // TODO: Add hyperlinks to Gaytor.rent official guides
 
                                 ui.label("Title:");
-                                ui.add(widgets::TextEdit::singleline(&mut self.title)
-                                    .desired_width(ui.available_width())
-                                    .hint_text("Descriptive title please!")
-                                );
+                                ui.vertical(|ui| {
+                                    ui.add(widgets::TextEdit::singleline(&mut self.title)
+                                        .desired_width(ui.available_width())
+                                        .hint_text("Descriptive title please!")
+                                    );
+                                    show_issues(ui, &validate_title(&self.title));
+                                });
 
                                 ui.end_row();
-                                ui.with_layout(Layout::left_to_right(Align::Min), |ui| {
+                                ui.with_layout(Layout::top_down(Align::Min), |ui| {
                                     ui.label("Description:");
-                                });
-                                ui.allocate_ui(vec2(ui.available_size_before_wrap().x, 200.), |ui| {
-                                    ScrollArea::vertical()
-                                        .always_show_scroll(true)
-                                        .stick_to_bottom(true)
-                                        .show(ui, |ui| {
-                                            ui.add_sized(ui.available_size_before_wrap(),
-                                                         widgets::TextEdit::multiline(&mut self.description)
-                                                             .desired_width(ui.available_width())
-                                                             .hint_text("(HTML/BB code not allowed)"),
-                                            );
+                                    ui.horizontal(|ui| {
+                                        let mut applied_template = None;
+                                        egui::ComboBox::from_id_source("template")
+                                            .selected_text("Template")
+                                            .width(70.)
+                                            .show_ui(ui, |ui| {
+                                                for template in self.templates.iter() {
+                                                    if ui.selectable_label(false, &template.name).clicked() {
+                                                        applied_template = Some(template.clone());
+                                                    }
+                                                }
+                                            });
+                                        if ui.button("⚙").on_hover_text("Manage templates").clicked() {
+                                            self.is_template_menu_open = true;
+                                        }
+                                        if let Some(template) = applied_template {
+                                            if self.description.trim().is_empty() {
+                                                self.apply_template(&template);
+                                            } else {
+                                                self.template_to_apply = Some(template);
+                                            }
+                                        }
+                                    });
+                                    if let Some(template) = self.template_to_apply.clone() {
+                                        ui.colored_label(
+                                            Severity::Warning.to_color(),
+                                            format!("Replace the description with \"{}\"?", template.name),
+                                        );
+                                        ui.horizontal(|ui| {
+                                            if ui.button("Replace").clicked() {
+                                                self.template_to_apply = None;
+                                                self.apply_template(&template);
+                                            }
+                                            if ui.button("Cancel").clicked() {
+                                                self.template_to_apply = None;
+                                            }
                                         });
+                                    }
+                                });
+                                ui.vertical(|ui| {
+                                    ui.allocate_ui(vec2(ui.available_size_before_wrap().x, 200.), |ui| {
+                                        ScrollArea::vertical()
+                                            .always_show_scroll(true)
+                                            .stick_to_bottom(true)
+                                            .show(ui, |ui| {
+                                                ui.add_sized(ui.available_size_before_wrap(),
+                                                             widgets::TextEdit::multiline(&mut self.description)
+                                                                 .desired_width(ui.available_width())
+                                                                 .hint_text("(HTML/BB code not allowed)"),
+                                                );
+                                            });
+                                    });
+                                    show_issues(ui, &validate_description(&self.description));
                                 });
 
                                 ui.end_row();
@@ -496,10 +2797,18 @@ impl eframe::App for Qtm {
                                                    if ui.add(Tag::new(&TagData {
                                                        text: "➕".to_owned(),
                                                        color: TagColor::BlueGrey,
+                                                       aliases: Vec::new(),
                                                    }, &mut self.is_tag_menu_open)).clicked() {
                                                        self.is_tag_menu_open = !self.is_tag_menu_open;
+                                                       self.tag_query.clear();
+                                                       self.tag_cursor = 0;
+                                                       ui.memory_mut(|m| m.request_focus(Id::new("tag_query")));
                                                    }
                                                });
+                                ui.end_row();
+
+                                ui.label("Suggestions:");
+                                self.show_suggestions(ui);
                             });
                         ui.add_space(20.);
                     });
@@ -512,34 +2821,12 @@ impl eframe::App for Qtm {
                         .rounding(Rounding::same(10.))
                         .inner_margin(Margin::same(10.)),
                 )
-                .fixed_size(vec2(400., 250.))
+                .fixed_size(vec2(450., 420.))
                 .default_pos(ctx.pointer_latest_pos().unwrap_or_default())
                 .title_bar(false)
                 .drag_bounds(ctx.screen_rect())
                 .show(ctx, |ui| {
-                    ui.with_layout(
-                        Layout::left_to_right(Align::TOP).with_main_wrap(true),
-                        |ui| {
-                            ui.style_mut().spacing.item_spacing = vec2(10., 10.);
-                            for mut tag_state in self.tags.iter_mut() {
-                                if ui.add(Tag::from(&mut tag_state)).clicked() {
-                                    *tag_state.1 = !*tag_state.1;
-                                }
-                            }
-                            ui.add_space(ui.available_size_before_wrap().x);
-                            ui.label(" ");
-                            ui.add_space(ui.available_size_before_wrap().x);
-
-                            self.custom_tags.retain(|tag_data, is_selected | {
-                                let mut state = (tag_data, is_selected);
-                                let response = ui.add(Tag::from(&mut state));
-                                if response.clicked() {
-                                    *state.1 = !*state.1;
-                                }
-                                !response.secondary_clicked()
-                            });
-                        },
-                    );
+                    self.show_tag_list(ui);
                     ui.with_layout(Layout::bottom_up(Align::Center), |ui| {
                         if ui
                             .add_sized(
@@ -550,6 +2837,14 @@ impl eframe::App for Qtm {
                         {
                             self.is_tag_menu_open = false;
                         }
+                        ui.horizontal(|ui| {
+                            if ui.button("Import").on_hover_text("Import a shared custom tag set").clicked() {
+                                self.import_custom_tags();
+                            }
+                            if ui.button("Export").on_hover_text("Export custom tags to share them").clicked() {
+                                self.export_custom_tags();
+                            }
+                        });
                         ui.add_space(5.);
                         ui.separator();
 
@@ -571,6 +2866,7 @@ impl eframe::App for Qtm {
 
                                 let response = ui.add(
                                     widgets::TextEdit::singleline(&mut self.new_custom_tag.text)
+                                        .id(Id::new("new_custom_tag"))
                                         .text_color(self.new_custom_tag.color.to_secondary_color())
                                         .desired_width(150.)
                                         .margin(vec2(10., 4.)),
@@ -587,9 +2883,11 @@ impl eframe::App for Qtm {
                                             );
                                         }
                                     });
-                            if (response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter))) ||
-                                ui.add(widgets::Button::new("➕".to_owned())).clicked() {
+                            if ((response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter))) ||
+                                ui.add(widgets::Button::new("➕".to_owned())).clicked()) &&
+                                !self.new_custom_tag.text.trim().is_empty() {
                                     self.custom_tags.insert(self.new_custom_tag.clone(), false);
+                                    self.save_custom_tags();
                                 }
                             });
                         });
@@ -597,5 +2895,16 @@ impl eframe::App for Qtm {
                     ui.allocate_space(ui.available_size());
                 });
         }
+
+        if self.last_autosave.elapsed() >= AUTOSAVE_INTERVAL {
+            self.autosave();
+            self.save_config_if_dirty();
+        }
+        ctx.request_repaint_after(AUTOSAVE_INTERVAL);
+    }
+
+    fn on_exit(&mut self, _gl: Option<&eframe::glow::Context>) {
+        self.autosave();
+        self.save_config_if_dirty();
     }
 }
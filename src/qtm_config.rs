@@ -1,6 +1,7 @@
 // SPDX-License-Identifier: BSD-2-Clause-Patent
 
 use std::cmp::Ordering;
+use std::collections::BTreeMap;
 use std::fmt::Formatter;
 use std::fs;
 use std::ops::Neg;
@@ -11,12 +12,27 @@ use strum_macros::Display;
 use tracing::{info, warn};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct QtmConfig {
     pub version: QtmVersion,
     pub theme: QtmTheme,
     pub default_directory: Option<PathBuf>,
     pub initial_window_size: (usize, usize),
     pub image_area: usize,
+    // JSON list of the official tags, either as names or as objects with a `name`/`text` field
+    pub tag_source_url: String,
+    // Upload page whose category list maps categories to the values submitted by the form
+    pub category_source_url: String,
+    // Most recently selected tags first
+    pub recent_tags: Vec<String>,
+    // Keywords in the content's name, file names or title that suggest a category (by name)
+    pub category_keywords: BTreeMap<String, String>,
+    // Categories (by name) pinned to the top of the category drop-downs
+    pub favourite_categories: Vec<String>,
+    // Whether preprocessing keeps the EXIF/XMP/ICC metadata of images that need no re-encoding
+    pub keep_image_metadata: bool,
+    pub site_profile: SiteProfile,
+    pub image_host: ImageHostConfig,
 }
 
 impl Default for QtmConfig {
@@ -27,6 +43,34 @@ impl Default for QtmConfig {
             default_directory: None,
             initial_window_size: (800, 700),
             image_area: 120_000,
+            tag_source_url: "https://www.gaytorrent.ru/tags.json".to_owned(),
+            category_source_url: "https://www.gaytorrent.ru/upload.php".to_owned(),
+            recent_tags: Vec::new(),
+            category_keywords: [
+                ("onlyfans", "Fan Sites"),
+                ("fansly", "Fan Sites"),
+                ("justforfans", "Fan Sites"),
+                ("friends 2 follow", "Fan Sites"),
+                ("chaturbate", "Amateur"),
+                ("homemade", "Homemade"),
+                ("bareback", "Bareback"),
+                ("bdsm", "BDSM"),
+                ("solo", "Solo"),
+                ("twink", "Twinks"),
+                ("twinks", "Twinks"),
+                ("bear", "Bears"),
+                ("bears", "Bears"),
+                ("vintage", "Vintage"),
+                ("yaoi", "Comic & Yaoi"),
+                ("wrestling", "Wrestling and Sports"),
+            ]
+            .into_iter()
+            .map(|(keyword, category)| (keyword.to_owned(), category.to_owned()))
+            .collect(),
+            favourite_categories: Vec::new(),
+            keep_image_metadata: false,
+            site_profile: SiteProfile::default(),
+            image_host: ImageHostConfig::default(),
         }
     }
 }
@@ -72,6 +116,51 @@ impl QtmConfig {
     }
 }
 
+/// Limits imposed by the site on uploads
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SiteProfile {
+    pub max_image_width: u32,
+    pub max_image_height: u32,
+    // In bytes
+    pub max_image_size: u64,
+}
+
+impl Default for SiteProfile {
+    fn default() -> Self {
+        Self {
+            max_image_width: 2048,
+            max_image_height: 2048,
+            max_image_size: 2_000_000,
+        }
+    }
+}
+
+/// An image host which takes the image as a multipart form and replies with JSON
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ImageHostConfig {
+    // Empty if no image host is configured
+    pub endpoint: String,
+    // Name of the form field holding the image
+    pub field_name: String,
+    // Dot-separated path to the URL in the response, e.g. "data.url" or "files.0.url"
+    pub url_path: String,
+    // e.g. "Authorization: Client-ID 0123456789"; empty if not needed
+    pub auth_header: String,
+}
+
+impl Default for ImageHostConfig {
+    fn default() -> Self {
+        Self {
+            endpoint: String::new(),
+            field_name: "image".to_owned(),
+            url_path: "data.url".to_owned(),
+            auth_header: String::new(),
+        }
+    }
+}
+
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize, Display)]
 pub enum QtmTheme {
     Light,
@@ -140,6 +229,24 @@ mod tests {
         assert_ne!(QtmVersion(0, 3, 6), QtmVersion(1, 3, 5));
     }
 
+    #[test]
+    fn test_config_round_trip() {
+        let config = QtmConfig {
+            recent_tags: vec!["Onlyfans".to_owned()],
+            favourite_categories: vec!["Solo".to_owned()],
+            ..Default::default()
+        };
+        let deserialised = toml::from_str::<QtmConfig>(&toml::to_string(&config).unwrap()).unwrap();
+        assert_eq!(deserialised.recent_tags, config.recent_tags);
+        assert_eq!(deserialised.category_keywords, config.category_keywords);
+        assert_eq!(deserialised.site_profile, config.site_profile);
+
+        // Missing fields fall back to their defaults
+        let partial = toml::from_str::<QtmConfig>("image_area = 1000").unwrap();
+        assert_eq!(partial.image_area, 1000);
+        assert_eq!(partial.site_profile, SiteProfile::default());
+    }
+
     #[test]
     fn test_version_cmp() {
         assert!(QtmVersion(0, 3, 0) < QtmVersion(1, 3, 0));
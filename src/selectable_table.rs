@@ -1,6 +1,17 @@
 // SPDX-License-Identifier: BSD-2-Clause-Patent
 
-use eframe::egui::{vec2, Align, Id, Layout, Rect, Response, ScrollArea, Sense, Ui};
+use std::collections::BTreeSet;
+use std::ops::Range;
+
+use eframe::egui::{
+    pos2, vec2, widgets, Align, Context, CursorIcon, Id, Key, Layout, Modifiers, Rect, Response,
+    RichText, ScrollArea, Sense, Stroke, Ui,
+};
+use serde::{Deserialize, Serialize};
+
+const MIN_COLUMN_WIDTH: f32 = 10.;
+// Width of the area around a column border which can be dragged
+const RESIZE_HANDLE_WIDTH: f32 = 6.;
 
 #[derive(Debug, Copy, Clone, PartialEq, Default)]
 pub enum ColumnWidthType {
@@ -8,26 +19,46 @@ pub enum ColumnWidthType {
     // fixed column width
     #[default]
     Remainder, // divide all remaining widths between remainders
+    Auto,         // fit the widest cell, as measured on the previous frame
+    Percent(f32), // percentage of the width available to the columns
 }
 
 #[derive(Debug, Copy, Clone, PartialEq, Default)]
 pub struct Column {
     width_type: ColumnWidthType,
     layout: Option<Layout>,
+    resizable: bool,
 }
 
 impl Column {
     pub fn fixed(width: f32) -> Self {
         Self {
             width_type: ColumnWidthType::Fixed(width),
-            layout: None,
+            ..Default::default()
         }
     }
 
     pub fn remainder() -> Self {
         Self {
             width_type: ColumnWidthType::Remainder,
-            layout: None,
+            ..Default::default()
+        }
+    }
+
+    pub fn auto() -> Self {
+        Self {
+            width_type: ColumnWidthType::Auto,
+            ..Default::default()
+        }
+    }
+
+    /// A column taking `percent` (0 to 100) of the width available to the columns
+    // Not used by the image table, whose columns are sized by their contents
+    #[allow(dead_code)]
+    pub fn percent(percent: f32) -> Self {
+        Self {
+            width_type: ColumnWidthType::Percent(percent),
+            ..Default::default()
         }
     }
 
@@ -35,6 +66,12 @@ impl Column {
         self.layout = Some(layout);
         self
     }
+
+    /// Allow the user to drag the right border of the column in the header
+    pub fn resizable(mut self, resizable: bool) -> Self {
+        self.resizable = resizable;
+        self
+    }
 }
 
 pub struct TableBuilder {
@@ -104,55 +141,94 @@ impl TableBuilder {
         self.ui.available_rect_before_wrap().width() - self.scroll_width()
     }
 
-    /// Return the widths of columns and the total width of the table
-    fn widths(&self) -> (Vec<f32>, f32) {
-        let available_width = self.avaialble_width();
-        let mut remainder_count = 0;
-        let mut remaining_width =
-            available_width - self.ui.spacing().item_spacing.x * (self.columns.len() - 1) as f32;
-
-        self.columns
+    /// Return the widths of `columns` and their total width including `spacing` between them,
+    /// given the `available_width` and the `content_widths` of auto columns. Remainder columns
+    /// shrink to nothing if the other columns take more than the available width.
+    fn widths(
+        columns: &[Column],
+        available_width: f32,
+        spacing: f32,
+        content_widths: &[f32],
+    ) -> (Vec<f32>, f32) {
+        let total_spacing = spacing * columns.len().saturating_sub(1) as f32;
+        let column_width = (available_width - total_spacing).max(0.);
+        let mut widths: Vec<Option<f32>> = columns
             .iter()
-            .for_each(|Column { width_type, .. }| match width_type {
-                ColumnWidthType::Fixed(width) => remaining_width -= width,
-                ColumnWidthType::Remainder => remainder_count += 1,
-            });
-
-        (
-            self.columns
-                .iter()
-                .map(|Column { width_type, .. }| match width_type {
-                    ColumnWidthType::Fixed(width) => *width,
-                    ColumnWidthType::Remainder => {
-                        if remaining_width > 0. {
-                            remaining_width / remainder_count as f32
-                        } else {
-                            0.
-                        }
-                    }
-                })
-                .collect(),
-            self.ui.available_rect_before_wrap().width()
-                - if remainder_count == 0 {
-                    remaining_width
-                } else {
-                    0.
-                },
-        )
+            .enumerate()
+            .map(|(index, Column { width_type, .. })| match width_type {
+                ColumnWidthType::Fixed(width) => Some(*width),
+                ColumnWidthType::Auto => {
+                    Some(content_widths.get(index).copied().unwrap_or_default())
+                }
+                ColumnWidthType::Percent(percent) => Some(column_width * percent / 100.),
+                ColumnWidthType::Remainder => None,
+            })
+            .collect();
+
+        let remainder_count = widths.iter().filter(|width| width.is_none()).count();
+        let remaining_width = column_width - widths.iter().flatten().sum::<f32>();
+        let remainder_width = if remainder_count > 0 && remaining_width > 0. {
+            remaining_width / remainder_count as f32
+        } else {
+            0.
+        };
+        let widths: Vec<f32> = widths
+            .iter_mut()
+            .map(|width| width.unwrap_or(remainder_width))
+            .collect();
+        let total_width = widths.iter().sum::<f32>() + total_spacing;
+        (widths, total_width)
     }
 
     pub fn build(self) -> Table {
-        let widths = self.widths();
+        let ctx = self.ui.ctx();
+        let resized_widths: Vec<Option<f32>> = ctx.data_mut(|d| {
+            d.get_persisted(self.id.with("column_widths"))
+                .unwrap_or_default()
+        });
+        let content_widths: Vec<f32> = ctx.data_mut(|d| {
+            d.get_temp(self.id.with("content_widths"))
+                .unwrap_or_default()
+        });
+        // Resized columns keep the width they were dragged to
+        let columns: Vec<Column> = self
+            .columns
+            .iter()
+            .enumerate()
+            .map(|(index, column)| match resized_widths.get(index) {
+                Some(Some(width)) => Column {
+                    width_type: ColumnWidthType::Fixed(*width),
+                    ..*column
+                },
+                _ => *column,
+            })
+            .collect();
+        let (column_widths, total_width) = Self::widths(
+            &columns,
+            self.avaialble_width(),
+            self.ui.spacing().item_spacing.x,
+            &content_widths,
+        );
 
         Table {
-            ui: self.ui,
-            total_width: widths.1,
-            column_widths: widths.0,
+            total_width: total_width + self.scroll_width(),
+            column_widths,
             column_layouts: self
                 .columns
                 .iter()
                 .map(|Column { layout, .. }| layout.unwrap_or(self.layout))
                 .collect(),
+            auto_columns: self
+                .columns
+                .iter()
+                .map(|column| column.width_type == ColumnWidthType::Auto)
+                .collect(),
+            resizable_columns: self.columns.iter().map(|column| column.resizable).collect(),
+            content_widths: vec![0.; self.columns.len()],
+            previous_content_widths: content_widths,
+            sort: ctx.data_mut(|d| d.get_persisted(self.id.with("sort"))),
+            sort_event: None,
+            ui: self.ui,
             striped: self.striped,
             vscroll: self.vscroll,
             id: self.id,
@@ -160,11 +236,32 @@ impl TableBuilder {
     }
 }
 
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SortDirection {
+    Ascending,
+    Descending,
+}
+
+/// The column the user last sorted the table by with the headers
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Sort {
+    pub column: usize,
+    pub direction: SortDirection,
+}
+
 pub struct Table {
     ui: Ui,
     total_width: f32,
     column_widths: Vec<f32>,
     column_layouts: Vec<Layout>,
+    auto_columns: Vec<bool>,
+    resizable_columns: Vec<bool>,
+    // Measured on this frame, and used for the widths of auto columns on the next
+    content_widths: Vec<f32>,
+    previous_content_widths: Vec<f32>,
+    sort: Option<Sort>,
+    // Set when a header is clicked
+    sort_event: Option<Sort>,
     striped: bool,
     vscroll: bool,
     id: Id,
@@ -183,61 +280,366 @@ impl Table {
 
     /// Support multi-row header
     pub fn header(mut self, height: f32, add_header_row: impl FnOnce(TableRow)) -> Self {
+        let rect = self.ui.available_rect_before_wrap();
+        let mut header = HeaderState {
+            sort: self.sort,
+            is_clicked: false,
+        };
         add_header_row(TableRow {
-            ui: &mut self.ui.child_ui(
-                self.ui.available_rect_before_wrap(),
-                Layout::left_to_right(Align::Min),
-            ),
+            ui: &mut self.ui.child_ui(rect, Layout::left_to_right(Align::Min)),
             widths: &self.column_widths,
             layout: &self.column_layouts,
+            auto_columns: &self.auto_columns,
+            content_widths: &mut self.content_widths,
+            header: Some(&mut header),
             height,
             column_no: 0,
         });
+        if let (true, Some(sort)) = (header.is_clicked, header.sort) {
+            self.sort = Some(sort);
+            self.sort_event = Some(sort);
+            self.ui
+                .data_mut(|d| d.insert_persisted(self.id.with("sort"), sort));
+        }
+        self.resize_columns(Rect::from_min_size(
+            rect.min,
+            vec2(self.total_width, height),
+        ));
         self.ui
             .allocate_exact_size(vec2(self.total_width, height), SENSE_NONE);
         self.ui.separator();
+        self
+    }
 
-        Self {
-            ui: self.ui,
-            total_width: self.total_width,
-            column_widths: self.column_widths,
-            column_layouts: self.column_layouts,
-            striped: self.striped,
-            vscroll: self.vscroll,
-            id: self.id,
+    /// Add the handles on the borders of the resizable columns in the header at `rect`
+    fn resize_columns(&mut self, rect: Rect) {
+        let spacing = self.ui.spacing().item_spacing.x;
+        let resized_id = self.id.with("column_widths");
+        let mut left = rect.left();
+        for (index, width) in self.column_widths.iter().enumerate() {
+            let border = left + width + spacing / 2.;
+            if self.resizable_columns[index] {
+                let handle = Rect::from_x_y_ranges(
+                    border - RESIZE_HANDLE_WIDTH / 2.0..=border + RESIZE_HANDLE_WIDTH / 2.,
+                    rect.y_range(),
+                );
+                let response =
+                    self.ui
+                        .interact(handle, resized_id.with(index), Sense::click_and_drag());
+                let mut resized_width = None;
+                if response.double_clicked() {
+                    // Back to the width the column was built with
+                    resized_width = Some(None);
+                } else if let Some(pointer) = response
+                    .dragged()
+                    .then(|| response.interact_pointer_pos())
+                    .flatten()
+                {
+                    resized_width = Some(Some((pointer.x - left).max(MIN_COLUMN_WIDTH)));
+                }
+                if let Some(resized_width) = resized_width {
+                    self.ui.data_mut(|d| {
+                        let resized_widths: &mut Vec<Option<f32>> =
+                            d.get_persisted_mut_or_default(resized_id);
+                        resized_widths.resize(self.column_widths.len(), None);
+                        resized_widths[index] = resized_width;
+                    });
+                    self.ui.ctx().request_repaint();
+                }
+
+                if response.hovered() || response.dragged() {
+                    self.ui.ctx().set_cursor_icon(CursorIcon::ResizeHorizontal);
+                    let stroke = if response.dragged() {
+                        self.ui.visuals().widgets.active.fg_stroke
+                    } else {
+                        self.ui.visuals().widgets.hovered.fg_stroke
+                    };
+                    self.ui.painter().vline(border, rect.y_range(), stroke);
+                }
+            }
+            left += width + spacing;
         }
     }
 
-    /// Return the index of the row being selected (if any)
-    /// determined by `add_body_contents`
-    pub fn body<F>(mut self, add_body_contents: F) -> Option<usize>
+    /// Lay out the rows added by `add_body_contents`, handling selection, drag-and-drop and
+    /// keyboard navigation
+    pub fn body<F>(mut self, add_body_contents: F) -> TableResponse
     where
-        F: for<'b> FnOnce(TableBody<'b>) -> Option<usize>,
+        F: for<'b> FnOnce(TableBody<'b>),
     {
-        let selected_row = self.ui.data_mut(|d| *d.get_persisted_mut_or(self.id, None));
-        let mut new_selected_row = selected_row;
-        ScrollArea::neither()
+        let ctx = self.ui.ctx().clone();
+        let dragged_id = self.id.with("dragged");
+        let row_count_id = self.id.with("row_count");
+        let mut state = BodyState {
+            selection: Selection::load(&ctx, self.id),
+            has_focus: ctx.memory(|m| m.has_focus(self.id)),
+            dragged: ctx.data_mut(|d| d.get_temp(dragged_id)),
+            content_widths: std::mem::take(&mut self.content_widths),
+            ..Default::default()
+        };
+        let old_selection = state.selection.clone();
+        let mut events: Vec<TableEvent> = self
+            .sort_event
+            .map(TableEvent::Sorted)
+            .into_iter()
+            .collect();
+
+        // The rows are not known until they are added, so the keys act on last frame's rows
+        if state.has_focus {
+            let row_count = ctx.data_mut(|d| d.get_temp(row_count_id).unwrap_or_default());
+            if let Some(event) = state.handle_keys(&ctx, row_count) {
+                events.push(event);
+            }
+        }
+
+        let output = ScrollArea::neither()
             .vscroll(self.vscroll)
             .auto_shrink([false; 2])
             .show(&mut self.ui, |ui| {
-                if let new_selected_row_inner @ Some(_) = add_body_contents(TableBody {
+                add_body_contents(TableBody {
                     ui,
                     total_width: self.total_width,
                     widths: &self.column_widths,
                     layout: &self.column_layouts,
+                    auto_columns: &self.auto_columns,
                     striped: self.striped,
-                    row_striped: true,
-                    row_no: 0,
-                    selected_row,
-                }) {
-                    new_selected_row = new_selected_row_inner;
-                };
+                    state: &mut state,
+                });
             });
-        if selected_row != new_selected_row {
-            self.ui
-                .data_mut(|d| d.insert_persisted(self.id, new_selected_row));
+
+        let response = self.ui.interact(
+            output.inner_rect,
+            self.id,
+            Sense::focusable_noninteractive(),
+        );
+        if state.is_clicked {
+            ctx.memory_mut(|m| m.request_focus(self.id));
+        } else if response.clicked_elsewhere() {
+            ctx.memory_mut(|m| m.surrender_focus(self.id));
+        }
+
+        if let Some(dragged) = state.dragged {
+            let (to, y) = state.drop_target.unwrap_or((state.row_count, state.bottom));
+            if ctx.input(|i| i.pointer.any_down()) {
+                let stroke = self.ui.visuals().selection.stroke;
+                self.ui.painter_at(output.inner_rect).hline(
+                    output.inner_rect.x_range(),
+                    y,
+                    Stroke::new(2., stroke.color),
+                );
+            } else {
+                state.dragged = None;
+                let rows: Vec<usize> = state.selection.rows.iter().copied().collect();
+                let moved = moved_rows(&rows, to);
+                // Dropping the rows where they already are is not a move
+                if dragged < state.row_count && !moved.clone().eq(rows.iter().copied()) {
+                    state.selection.set(moved);
+                    events.push(TableEvent::Moved { rows, to });
+                }
+            }
+        }
+        if state.content_widths != self.previous_content_widths {
+            ctx.request_repaint();
+        }
+        ctx.data_mut(|d| {
+            d.insert_temp(self.id.with("content_widths"), state.content_widths);
+            d.insert_temp(row_count_id, state.row_count);
+            match state.dragged {
+                Some(dragged) => d.insert_temp(dragged_id, dragged),
+                None => d.remove::<usize>(dragged_id),
+            }
+        });
+
+        state.selection.rows.retain(|row| *row < state.row_count);
+        if state.selection != old_selection {
+            state.selection.clone().store(&ctx, self.id);
+            // Rows above the changed ones were painted with the old selection
+            ctx.request_repaint();
+        }
+        TableResponse {
+            selection: state.selection,
+            events,
         }
-        new_selected_row
+    }
+}
+
+/// Forget the column the table with `id` is sorted by, e.g. once its rows have been reordered
+pub fn clear_sort(ctx: &Context, id: Id) {
+    ctx.data_mut(|d| d.remove::<Sort>(id.with("sort")));
+}
+
+/// The selected rows of a table, persisted in egui memory under the `Id` of the table
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Selection {
+    rows: BTreeSet<usize>,
+    // Where Shift+click and Shift+arrow select from
+    anchor: Option<usize>,
+    // The row last clicked or moved to with the keyboard
+    cursor: Option<usize>,
+}
+
+impl Selection {
+    pub fn load(ctx: &Context, id: Id) -> Self {
+        ctx.data_mut(|d| d.get_persisted(id).unwrap_or_default())
+    }
+
+    pub fn store(self, ctx: &Context, id: Id) {
+        ctx.data_mut(|d| d.insert_persisted(id, self));
+    }
+
+    pub fn rows(&self) -> &BTreeSet<usize> {
+        &self.rows
+    }
+
+    pub fn is_selected(&self, row: usize) -> bool {
+        self.rows.contains(&row)
+    }
+
+    /// Replace the selection with `rows`, moving the cursor to the last one
+    pub fn set(&mut self, rows: impl IntoIterator<Item = usize>) {
+        self.rows = rows.into_iter().collect();
+        self.anchor = self.rows.first().copied();
+        self.cursor = self.rows.last().copied();
+    }
+
+    /// Select `row` as if it were clicked with `modifiers`: Ctrl toggles it and Shift selects the
+    /// range from the anchor
+    fn select(&mut self, row: usize, modifiers: Modifiers) {
+        if modifiers.shift {
+            let anchor = self.anchor.unwrap_or(row);
+            if !modifiers.command {
+                self.rows.clear();
+            }
+            self.rows.extend(anchor.min(row)..=anchor.max(row));
+            self.anchor = Some(anchor);
+        } else {
+            if !modifiers.command {
+                self.rows.clear();
+                self.rows.insert(row);
+            } else if !self.rows.remove(&row) {
+                self.rows.insert(row);
+            }
+            self.anchor = Some(row);
+        }
+        self.cursor = Some(row);
+    }
+}
+
+/// Something done to the rows, which the caller applies to its data
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TableEvent {
+    /// `rows` were dragged to before the row at `to`, or to the end if `to` is the row count; see
+    /// [`move_rows`]
+    Moved { rows: Vec<usize>, to: usize },
+    /// Delete was pressed with `rows` selected
+    Deleted(Vec<usize>),
+    /// A header was clicked to sort the rows
+    Sorted(Sort),
+}
+
+pub struct TableResponse {
+    pub selection: Selection,
+    pub events: Vec<TableEvent>,
+}
+
+/// Return the indices of `rows` after moving them to before `to`
+fn moved_rows(rows: &[usize], to: usize) -> Range<usize> {
+    let start = to - rows.iter().filter(|row| **row < to).count();
+    start..start + rows.len()
+}
+
+/// Move `rows` of `items`, keeping their order, to before the item at `to` (or to the end if `to`
+/// is the length), and return their new indices
+pub fn move_rows<T>(items: &mut Vec<T>, rows: &[usize], to: usize) -> Range<usize> {
+    let mut rows = rows.to_vec();
+    rows.sort_unstable();
+    rows.dedup();
+    let moved_indices = moved_rows(&rows, to);
+    let mut moved = Vec::with_capacity(rows.len());
+    for row in rows.iter().rev() {
+        moved.push(items.remove(*row));
+    }
+    moved.reverse();
+    items.splice(moved_indices.start..moved_indices.start, moved);
+    moved_indices
+}
+
+/// Return the indices of the `count` rows of `row_height` laid out from `top` which are at least
+/// partly between `clip_top` and `clip_bottom`
+fn visible_rows(
+    top: f32,
+    row_height: f32,
+    count: usize,
+    clip_top: f32,
+    clip_bottom: f32,
+) -> Range<usize> {
+    let first = (((clip_top - top) / row_height).floor().max(0.) as usize).min(count);
+    let last = (((clip_bottom - top) / row_height).ceil().max(0.) as usize).clamp(first, count);
+    first..last
+}
+
+#[derive(Default)]
+struct BodyState {
+    selection: Selection,
+    has_focus: bool,
+    is_clicked: bool,
+    // The row the selected rows are being dragged by
+    dragged: Option<usize>,
+    // Where the dragged rows would be dropped, and the y of the gap
+    drop_target: Option<(usize, f32)>,
+    // Set by the keyboard so the cursor stays visible
+    scroll_to: Option<usize>,
+    row_count: usize,
+    bottom: f32,
+    content_widths: Vec<f32>,
+}
+
+impl BodyState {
+    fn handle_keys(&mut self, ctx: &Context, row_count: usize) -> Option<TableEvent> {
+        let last = row_count.checked_sub(1)?;
+        let (target, modifiers) = ctx.input_mut(|i| {
+            let modifiers = i.modifiers;
+            let cursor = self.cursor_or_first();
+            let target = if i.consume_key(modifiers, Key::ArrowUp) {
+                Some(cursor.map_or(0, |cursor| cursor.saturating_sub(1)))
+            } else if i.consume_key(modifiers, Key::ArrowDown) {
+                Some(cursor.map_or(0, |cursor| (cursor + 1).min(last)))
+            } else if i.consume_key(modifiers, Key::Home) {
+                Some(0)
+            } else if i.consume_key(modifiers, Key::End) {
+                Some(last)
+            } else {
+                None
+            };
+            (target, modifiers)
+        });
+        if let Some(target) = target {
+            // Ctrl only matters for clicks
+            self.selection.select(
+                target.min(last),
+                Modifiers {
+                    shift: modifiers.shift,
+                    ..Default::default()
+                },
+            );
+            self.scroll_to = Some(target);
+            return None;
+        }
+
+        if !self.selection.rows.is_empty()
+            && ctx.input_mut(|i| i.consume_key(Modifiers::NONE, Key::Delete))
+        {
+            let rows = self.selection.rows.iter().copied().collect();
+            self.selection = Selection::default();
+            return Some(TableEvent::Deleted(rows));
+        }
+        None
+    }
+
+    fn cursor_or_first(&self) -> Option<usize> {
+        self.selection
+            .cursor
+            .or_else(|| self.selection.rows.first().copied())
     }
 }
 
@@ -246,10 +648,9 @@ pub struct TableBody<'a> {
     total_width: f32,
     widths: &'a [f32],
     layout: &'a [Layout],
+    auto_columns: &'a [bool],
     striped: bool,
-    row_striped: bool,
-    row_no: usize,
-    selected_row: Option<usize>,
+    state: &'a mut BodyState,
 }
 
 impl<'a> TableBody<'a> {
@@ -257,58 +658,185 @@ impl<'a> TableBody<'a> {
         self.ui
     }
 
-    pub fn row(&mut self, height: f32, add_row_content: impl FnOnce(TableRow)) -> Response {
-        let clicked = self.ui.interact(
-            Rect::from_min_size(
-                self.ui.next_widget_position(),
+    /// Add `count` rows of the same `height`, calling `add_row_content` with the index of each
+    /// visible row, and return their responses. The rows outside the scroll area only take up
+    /// space, so auto columns fit the visible rows.
+    pub fn rows(
+        &mut self,
+        height: f32,
+        count: usize,
+        mut add_row_content: impl FnMut(usize, TableRow),
+    ) -> Vec<(usize, Response)> {
+        let first_row_no = self.state.row_count;
+        let spacing = self.ui.spacing().item_spacing.y;
+        let row_height = height + spacing;
+        let top = self.ui.next_widget_position().y;
+        let clip_rect = self.ui.clip_rect();
+        let Range { start: first, end: last } =
+            visible_rows(top, row_height, count, clip_rect.top(), clip_rect.bottom());
+
+        // The keyboard cursor may have moved to a row which is not laid out
+        if let Some(index) = self
+            .state
+            .scroll_to
+            .and_then(|row_no| row_no.checked_sub(first_row_no))
+            .filter(|index| *index < count)
+        {
+            let rect = Rect::from_min_size(
+                pos2(clip_rect.left(), top + index as f32 * row_height),
                 vec2(self.total_width, height),
-            ),
-            self.ui.id().with(self.row_no),
-            Sense::click(),
+            );
+            self.ui.scroll_to_rect(rect, None);
+        }
+
+        self.ui.add_space(first as f32 * row_height);
+        let responses = (first..last)
+            .map(|index| {
+                let response = self.add_row(first_row_no + index, height, |row| {
+                    add_row_content(index, row)
+                });
+                (index, response)
+            })
+            .collect();
+        self.ui.add_space((count - last) as f32 * row_height);
+        self.state.row_count = first_row_no + count;
+        self.state.bottom = top + count as f32 * row_height - spacing;
+        responses
+    }
+
+    fn add_row(
+        &mut self,
+        row_no: usize,
+        height: f32,
+        add_row_content: impl FnOnce(TableRow),
+    ) -> Response {
+        let rect = Rect::from_min_size(
+            self.ui.next_widget_position(),
+            vec2(self.total_width, height),
         );
+        let response = self
+            .ui
+            .interact(rect, self.ui.id().with(row_no), Sense::click_and_drag());
+        let state = &mut *self.state;
+        if response.clicked() {
+            state
+                .selection
+                .select(row_no, self.ui.input(|i| i.modifiers));
+            state.is_clicked = true;
+        }
+        if response.drag_started() {
+            // Dragging an unselected row moves only that row
+            if !state.selection.is_selected(row_no) {
+                state.selection.select(row_no, Modifiers::NONE);
+            }
+            state.dragged = Some(row_no);
+            state.is_clicked = true;
+        }
+        if state.dragged.is_some() && state.drop_target.is_none() {
+            // Drop before the first row whose upper half is below the pointer
+            let pointer = self.ui.input(|i| i.pointer.interact_pos());
+            if pointer.is_some_and(|pointer| pointer.y < rect.center().y) {
+                state.drop_target = Some((row_no, rect.top()));
+            }
+        }
+        if state.scroll_to == Some(row_no) {
+            self.ui.scroll_to_rect(rect, None);
+        }
+
         self.ui.allocate_ui_with_layout(
             vec2(self.total_width, height),
             Layout::left_to_right(Align::Center),
             |ui| {
-                if self.selected_row.is_some() && self.selected_row.unwrap() == self.row_no {
+                if state.selection.is_selected(row_no) {
                     ui.painter().rect_filled(
                         ui.available_rect_before_wrap(),
                         0.,
                         ui.visuals().extreme_bg_color,
                     );
-                } else if self.striped && self.row_striped {
+                } else if self.striped && row_no.is_multiple_of(2) {
                     ui.painter().rect_filled(
                         ui.available_rect_before_wrap(),
                         0.,
                         ui.visuals().faint_bg_color,
                     );
                 }
+                if state.has_focus && state.selection.cursor == Some(row_no) {
+                    ui.painter().rect_stroke(
+                        ui.available_rect_before_wrap().shrink(1.),
+                        0.,
+                        ui.visuals().selection.stroke,
+                    );
+                }
 
                 add_row_content(TableRow {
                     ui,
                     widths: self.widths,
                     layout: self.layout,
+                    auto_columns: self.auto_columns,
+                    content_widths: &mut state.content_widths,
+                    header: None,
                     height,
                     column_no: 0,
                 });
-
-                self.row_striped = !self.row_striped;
-                self.row_no += 1;
             },
         );
-        clicked
+        state.row_count = row_no + 1;
+        state.bottom = rect.bottom();
+        response
     }
 }
 
+struct HeaderState {
+    sort: Option<Sort>,
+    is_clicked: bool,
+}
+
 pub struct TableRow<'a> {
     ui: &'a mut Ui,
     widths: &'a [f32],
     layout: &'a [Layout],
+    auto_columns: &'a [bool],
+    content_widths: &'a mut [f32],
+    // Only in the header
+    header: Option<&'a mut HeaderState>,
     height: f32,
     column_no: usize,
 }
 
 impl<'a> TableRow<'a> {
+    /// Add a header cell which sorts the table by its column when clicked, or reverses the order
+    /// if it is already sorted by it
+    pub fn sort_col(&mut self, text: &str) {
+        let column = self.column_no;
+        let Some(header) = self.header.take() else {
+            panic!("adding a sortable column outside the header");
+        };
+        let direction = header
+            .sort
+            .filter(|sort| sort.column == column)
+            .map(|sort| sort.direction);
+        self.col(|ui| {
+            let arrow = match direction {
+                Some(SortDirection::Ascending) => " ⏶",
+                Some(SortDirection::Descending) => " ⏷",
+                None => "",
+            };
+            let label = widgets::Label::new(RichText::new(format!("{text}{arrow}")).strong())
+                .sense(Sense::click());
+            if ui.add(label).on_hover_text("Sort").clicked() {
+                header.sort = Some(Sort {
+                    column,
+                    direction: match direction {
+                        Some(SortDirection::Ascending) => SortDirection::Descending,
+                        _ => SortDirection::Ascending,
+                    },
+                });
+                header.is_clicked = true;
+            }
+        });
+        self.header = Some(header);
+    }
+
     pub fn col(&mut self, add_cell_contents: impl FnOnce(&mut Ui)) {
         if self.column_no >= self.widths.len() {
             panic!("adding more column than specified in table");
@@ -329,8 +857,95 @@ impl<'a> TableRow<'a> {
         let mut old_rect = ui.clip_rect();
         old_rect.max.x = rect.max.x;
         ui.set_clip_rect(old_rect);
+        if self.auto_columns[self.column_no] {
+            // Otherwise the text would wrap to the width measured on the previous frame
+            ui.style_mut().wrap = Some(false);
+        }
         add_cell_contents(&mut ui);
+        let content_width = &mut self.content_widths[self.column_no];
+        *content_width = content_width.max(ui.min_rect().width());
 
         self.column_no += 1;
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_select() {
+        let mut selection = Selection::default();
+        selection.select(2, Modifiers::NONE);
+        selection.select(5, Modifiers::SHIFT);
+        assert_eq!(selection.rows, BTreeSet::from([2, 3, 4, 5]));
+        selection.select(3, Modifiers::COMMAND);
+        selection.select(8, Modifiers::COMMAND);
+        assert_eq!(selection.rows, BTreeSet::from([2, 4, 5, 8]));
+        // The range starts at the last row clicked without Shift
+        selection.select(6, Modifiers::SHIFT);
+        assert_eq!(selection.rows, BTreeSet::from([6, 7, 8]));
+        selection.select(1, Modifiers::NONE);
+        assert_eq!(selection.rows, BTreeSet::from([1]));
+    }
+
+    #[test]
+    fn test_move_rows() {
+        let mut items = vec!['a', 'b', 'c', 'd', 'e', 'f'];
+        assert_eq!(move_rows(&mut items, &[4, 1], 0), 0..2);
+        assert_eq!(items, ['b', 'e', 'a', 'c', 'd', 'f']);
+        assert_eq!(move_rows(&mut items, &[0, 3], 6), 4..6);
+        assert_eq!(items, ['e', 'a', 'd', 'f', 'b', 'c']);
+        assert_eq!(move_rows(&mut items, &[2], 4), 3..4);
+        assert_eq!(items, ['e', 'a', 'f', 'd', 'b', 'c']);
+    }
+
+    #[test]
+    fn test_visible_rows() {
+        // Rows of 20 laid out from 100, with a 50 high clip rect
+        assert_eq!(visible_rows(100., 20., 10, 100., 150.), 0..3);
+        assert_eq!(visible_rows(100., 20., 10, 0., 50.), 0..0);
+        assert_eq!(visible_rows(100., 20., 10, 130., 180.), 1..4);
+        // The last row is only partly visible
+        assert_eq!(visible_rows(100., 20., 10, 260., 310.), 8..10);
+        // Scrolled past the end
+        assert_eq!(visible_rows(100., 20., 10, 400., 450.), 10..10);
+        assert_eq!(visible_rows(100., 20., 0, 100., 150.), 0..0);
+    }
+
+    #[test]
+    fn test_widths() {
+        let columns = [
+            Column::fixed(50.),
+            Column::remainder(),
+            Column::auto(),
+            Column::percent(25.),
+            Column::remainder(),
+        ];
+        let (widths, total_width) = TableBuilder::widths(&columns, 440., 10., &[0., 0., 30.]);
+        assert_eq!(widths, [50., 110., 30., 100., 110.]);
+        assert_eq!(total_width, 440.);
+
+        // Auto columns without a measurement yet, and more width than the fixed columns need
+        let columns = [Column::fixed(100.), Column::auto(), Column::fixed(100.)];
+        let (widths, total_width) = TableBuilder::widths(&columns, 500., 10., &[]);
+        assert_eq!(widths, [100., 0., 100.]);
+        assert_eq!(total_width, 220.);
+    }
+
+    #[test]
+    fn test_widths_overflow() {
+        let columns = [
+            Column::fixed(200.),
+            Column::remainder(),
+            Column::fixed(150.),
+        ];
+        let (widths, total_width) = TableBuilder::widths(&columns, 300., 10., &[]);
+        assert_eq!(widths, [200., 0., 150.]);
+        assert_eq!(total_width, 370.);
+
+        let (widths, total_width) = TableBuilder::widths(&[Column::remainder()], 0., 10., &[]);
+        assert_eq!(widths, [0.]);
+        assert_eq!(total_width, 0.);
+    }
+}
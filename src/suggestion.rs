@@ -0,0 +1,203 @@
+// SPDX-License-Identifier: BSD-2-Clause-Patent
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::BTreeMap;
+use std::hash::{Hash, Hasher};
+use std::path::Path;
+use std::str::FromStr;
+
+use tracing::warn;
+use walkdir::WalkDir;
+
+use crate::category::Category;
+use crate::tag::TagData;
+
+// Large folders are sampled rather than walked entirely; the first few names are representative
+const MAX_FILE_NAMES: usize = 200;
+
+/// Split `text` into lowercase alphanumeric words
+pub fn tokenise(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect()
+}
+
+/// Tokenise the name of the content, its parent folder and the names of the files within it
+pub fn tokenise_content<P: AsRef<Path>>(content_path: P) -> Vec<String> {
+    let content_path = content_path.as_ref();
+    let mut tokens: Vec<String> = content_path
+        .iter()
+        .rev()
+        .take(2)
+        .flat_map(|component| tokenise(&component.to_string_lossy()))
+        .collect();
+    tokens.extend(
+        WalkDir::new(content_path)
+            .min_depth(1)
+            .into_iter()
+            .filter_map(|entry| entry.ok())
+            .take(MAX_FILE_NAMES)
+            .flat_map(|entry| {
+                let path = entry.path();
+                tokenise(&path.file_stem().unwrap_or_default().to_string_lossy())
+            }),
+    );
+    tokens
+}
+
+/// Return whether `phrase` appears in `tokens` regardless of how it is split into words,
+/// e.g. "Just For Fans", "just_for_fans" and "JustForFans" all match each other
+fn contains_phrase(tokens: &[String], phrase: &str) -> bool {
+    let joined = tokenise(phrase).concat();
+    if joined.is_empty() {
+        return false;
+    }
+    (0..tokens.len()).any(|start| {
+        let mut concatenated = String::new();
+        tokens[start..]
+            .iter()
+            .map_while(|token| {
+                concatenated.push_str(token);
+                joined.starts_with(&concatenated).then_some(concatenated.len())
+            })
+            .any(|length| length == joined.len())
+    })
+}
+
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Suggestions {
+    pub tags: Vec<TagData>,
+    pub categories: Vec<Category>,
+}
+
+/// Suggest the tags whose text or aliases, and the categories whose keywords, appear in `tokens`
+///
+/// `category_keywords` maps keywords to category names; unknown category names are ignored.
+pub fn suggest<'a>(
+    tokens: &[String],
+    tags: impl IntoIterator<Item = &'a TagData>,
+    category_keywords: &BTreeMap<String, String>,
+) -> Suggestions {
+    let tags = tags
+        .into_iter()
+        .filter(|tag| {
+            contains_phrase(tokens, &tag.text)
+                || tag.aliases.iter().any(|alias| contains_phrase(tokens, alias))
+        })
+        .cloned()
+        .collect();
+
+    let mut categories = Vec::new();
+    for (keyword, name) in category_keywords {
+        if !contains_phrase(tokens, keyword) {
+            continue;
+        }
+        match Category::from_str(name) {
+            Ok(category) if category != Category::None => {
+                if !categories.contains(&category) {
+                    categories.push(category);
+                }
+            }
+            _ => warn!(keyword, name, "Unknown category in the category keywords"),
+        }
+    }
+
+    Suggestions { tags, categories }
+}
+
+/// The suggestions for the last title, content and tags, which are only recomputed when one of
+/// them changes
+#[derive(Debug, Default)]
+pub struct CachedSuggestions {
+    // Hash of everything the suggestions were computed from
+    key: Option<u64>,
+    suggestions: Suggestions,
+}
+
+impl CachedSuggestions {
+    pub fn get<'a, I>(
+        &mut self,
+        content_tokens: &[String],
+        title: &str,
+        tags: I,
+        category_keywords: &BTreeMap<String, String>,
+    ) -> &Suggestions
+    where
+        I: IntoIterator<Item = &'a TagData> + Clone,
+    {
+        let mut hasher = DefaultHasher::new();
+        content_tokens.hash(&mut hasher);
+        title.hash(&mut hasher);
+        tags.clone().into_iter().for_each(|tag| tag.hash(&mut hasher));
+        category_keywords.hash(&mut hasher);
+        let key = hasher.finish();
+
+        if self.key != Some(key) {
+            let mut tokens = content_tokens.to_vec();
+            tokens.extend(tokenise(title));
+            self.suggestions = suggest(&tokens, tags, category_keywords);
+            self.key = Some(key);
+        }
+        &self.suggestions
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tag::TagColor;
+
+    use super::*;
+
+    fn tag(text: &str, aliases: &[&str]) -> TagData {
+        TagData {
+            text: text.to_owned(),
+            color: TagColor::Blue,
+            aliases: aliases.iter().map(|alias| alias.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn test_suggest() {
+        let tags = [
+            tag("Onlyfans", &[]),
+            tag("Friends 2 Follow", &["F2F"]),
+            tag("JustForFans", &[]),
+            tag("Reddit", &[]),
+        ];
+        let keywords = BTreeMap::from([
+            ("onlyfans".to_owned(), "Fan Sites".to_owned()),
+            ("solo".to_owned(), "Solo".to_owned()),
+            ("bogus".to_owned(), "Not A Category".to_owned()),
+        ]);
+
+        let mut tokens = tokenise_content("/downloads/OnlyFans/some_creator_2023-01");
+        tokens.extend(tokenise("Solo set from Just For Fans (F2F bonus), not bogus"));
+        assert_eq!(
+            suggest(&tokens, &tags, &keywords),
+            Suggestions {
+                tags: vec![tags[0].clone(), tags[1].clone(), tags[2].clone()],
+                categories: vec![Category::FanSites, Category::Solo],
+            }
+        );
+        assert_eq!(suggest(&tokenise("Reddit-ish"), &tags, &keywords).tags.len(), 1);
+        assert_eq!(
+            suggest(&tokenise("Redditor"), &tags, &keywords),
+            Suggestions::default()
+        );
+    }
+
+    #[test]
+    fn test_cached_suggestions() {
+        let mut tags = vec![tag("Reddit", &[])];
+        let keywords = BTreeMap::new();
+        let tokens = tokenise("/downloads/reddit");
+        let mut cached = CachedSuggestions::default();
+        assert_eq!(cached.get(&tokens, "", &tags, &keywords).tags.len(), 1);
+        assert_eq!(cached.get(&tokens, "Onlyfans", &tags, &keywords).tags.len(), 1);
+
+        tags.push(tag("Onlyfans", &[]));
+        assert_eq!(cached.get(&tokens, "Onlyfans", &tags, &keywords).tags.len(), 2);
+        assert!(cached.get(&[], "", &tags, &keywords).tags.is_empty());
+    }
+}
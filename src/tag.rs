@@ -1,18 +1,26 @@
 // SPDX-License-Identifier: BSD-2-Clause-Patent
 
 use std::cmp::Ordering;
+use std::collections::{BTreeMap, BTreeSet};
 use std::fs;
 use std::path::Path;
+use std::time::Duration;
 
 use eframe::egui::{
     Button, Color32, FontId, Response, RichText, Rounding, Stroke, TextStyle, Ui, vec2,
     Widget,
 };
+use reqwest::blocking::Client;
+use reqwest::{header, StatusCode};
 use serde::{Deserialize, Serialize};
 use serde_repr::{Deserialize_repr, Serialize_repr};
+use sha2::{Digest, Sha256};
+use strum::IntoEnumIterator;
 use strum_macros::{Display, EnumIter};
 use tracing::{info, warn};
 
+const SYNC_TIMEOUT: Duration = Duration::from_secs(10);
+
 #[derive(Debug, Clone, Copy, Eq, PartialEq, Hash, Serialize_repr, Deserialize_repr, Display, EnumIter)]
 #[repr(u8)]
 // https://materialui.co/colors Material UI
@@ -44,6 +52,14 @@ pub enum TagColor {
 }
 
 impl TagColor {
+    /// Deterministically pick a colour for a tag that has none assigned
+    pub fn from_text(text: &str) -> Self {
+        let digest = Sha256::digest(text.as_bytes());
+        TagColor::iter()
+            .nth(digest[0] as usize % TagColor::iter().count())
+            .unwrap()
+    }
+
     pub const fn to_primary_color(self) -> Color32 {
         match self {
             TagColor::Red => Color32::from_rgb(244, 67, 54),
@@ -84,6 +100,102 @@ impl TagColor {
 pub struct TagData {
     pub text: String,
     pub color: TagColor,
+    // Alternative spellings or abbreviations, used when searching and suggesting tags
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub aliases: Vec<String>,
+}
+
+/// A user-defined tag, persisted with the number of times it has been selected
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct CustomTag {
+    #[serde(flatten)]
+    pub data: TagData,
+    #[serde(default)]
+    pub usage: u64,
+}
+
+/// How the tags in the tag menu are grouped when not searching
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Display, EnumIter)]
+pub enum TagGrouping {
+    Source,
+    #[strum(serialize = "Colour")]
+    Color,
+}
+
+/// Score how well `query` fuzzily matches `text`, or return `None` if it does not match at all
+///
+/// Every character of the query must appear in the text in order (case-insensitively); matches
+/// that are consecutive or at the start of a word score higher, and shorter texts are preferred.
+pub fn fuzzy_score(query: &str, text: &str) -> Option<i64> {
+    let query: Vec<char> = query
+        .to_lowercase()
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .collect();
+    if query.is_empty() {
+        return Some(0);
+    }
+    let text: Vec<char> = text.to_lowercase().chars().collect();
+
+    let mut score = 0;
+    let mut query_index = 0;
+    let mut previous_match = None;
+    for (index, c) in text.iter().enumerate() {
+        if query_index == query.len() {
+            break;
+        }
+        if *c == query[query_index] {
+            score += 10;
+            if index > 0 && previous_match == Some(index - 1) {
+                score += 50;
+            }
+            if index == 0 || !text[index - 1].is_alphanumeric() {
+                score += 30;
+            }
+            previous_match = Some(index);
+            query_index += 1;
+        }
+    }
+    (query_index == query.len()).then_some(score - text.len() as i64)
+}
+
+/// Validators of the last successful tag sync, stored next to the tag cache
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct TagSyncState {
+    etag: Option<String>,
+    hash: Option<String>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum TagSyncOutcome {
+    Unchanged,
+    Updated {
+        added: Vec<String>,
+        removed: Vec<String>,
+    },
+}
+
+/// A tag as listed by the site, either as a bare name or as an object
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum SiteTag {
+    Name(String),
+    Data {
+        #[serde(alias = "name")]
+        text: String,
+        #[serde(default)]
+        aliases: Vec<String>,
+    },
+}
+
+impl SiteTag {
+    /// Return the trimmed text and aliases of the tag
+    fn into_parts(self) -> (String, Vec<String>) {
+        match self {
+            SiteTag::Name(text) => (text.trim().to_owned(), Vec::new()),
+            SiteTag::Data { text, aliases } => (text.trim().to_owned(), aliases),
+        }
+    }
 }
 
 impl Ord for TagData {
@@ -99,63 +211,164 @@ impl PartialOrd for TagData {
 }
 
 impl TagData {
-    pub fn _init_data<P: AsRef<Path>>(path: P) {
+    /// Seed the tag cache with the bundled list; only used when no cache exists yet
+    pub fn init_data<P: AsRef<Path>>(path: P) {
         let tags = vec![
             TagData {
                 text: "Onlyfans".to_owned(),
                 color: TagColor::Blue,
+                aliases: Vec::new(),
             },
             TagData {
                 text: "Fansly".to_owned(),
                 color: TagColor::Blue,
+                aliases: Vec::new(),
             },
             TagData {
                 text: "JustForFans".to_owned(),
                 color: TagColor::Pink,
+                aliases: vec!["JFF".to_owned()],
             },
             TagData {
                 text: "Friends 2 Follow".to_owned(),
                 color: TagColor::DeepPurple,
+                aliases: vec!["F2F".to_owned(), "FriendsToFollow".to_owned()],
             },
             TagData {
                 text: "Pornhub".to_owned(),
                 color: TagColor::Orange,
+                aliases: vec!["PH".to_owned()],
             },
             TagData {
                 text: "Chaturbate".to_owned(),
                 color: TagColor::Amber,
+                aliases: vec!["CB".to_owned()],
             },
             TagData {
                 text: "Twitter".to_owned(),
                 color: TagColor::Blue,
+                aliases: Vec::new(),
             },
             TagData {
                 text: "Reddit".to_owned(),
                 color: TagColor::DeepOrange,
+                aliases: Vec::new(),
             },
             TagData {
                 text: "Amateur".to_owned(),
                 color: TagColor::Lime,
+                aliases: Vec::new(),
             },
             TagData {
                 text: "Snapchat".to_owned(),
                 color: TagColor::Yellow,
+                aliases: Vec::new(),
             },
             TagData {
                 text: "Tiktok".to_owned(),
                 color: TagColor::Cyan,
+                aliases: Vec::new(),
             },
             TagData {
                 text: "Instagram".to_owned(),
                 color: TagColor::Purple,
+                aliases: Vec::new(),
             },
         ];
         fs::write(path, serde_json::to_string(&tags).unwrap()).unwrap();
     }
 
+    /// Download the official tag list and update the cache at `path` if it has changed
+    ///
+    /// The ETag of the previous response is sent along so that the site may answer with
+    /// `304 Not Modified`; otherwise the content hash of the body is compared instead.
+    /// Colours of known tags are preserved, and new tags are assigned one by `TagColor::from_text`.
+    pub fn sync<P: AsRef<Path>>(client: &Client, url: &str, path: P) -> anyhow::Result<TagSyncOutcome> {
+        let path = path.as_ref();
+        let state_path = path.with_extension("sync.json");
+        let state = fs::read_to_string(&state_path)
+            .ok()
+            .and_then(|string| serde_json::from_str::<TagSyncState>(&string).ok())
+            .filter(|_| path.exists())
+            .unwrap_or_default();
+
+        let mut request = client.get(url).timeout(SYNC_TIMEOUT);
+        if let Some(etag) = &state.etag {
+            request = request.header(header::IF_NONE_MATCH, etag);
+        }
+        let response = request.send()?;
+        if response.status() == StatusCode::NOT_MODIFIED {
+            info!("Tags are up to date (not modified)");
+            return Ok(TagSyncOutcome::Unchanged);
+        }
+        let response = response.error_for_status()?;
+        let etag = response
+            .headers()
+            .get(header::ETAG)
+            .and_then(|etag| etag.to_str().ok())
+            .map(str::to_owned);
+        let body = response.bytes()?;
+        let hash = format!("{:x}", Sha256::digest(&body));
+
+        let outcome = if state.hash.as_deref() == Some(hash.as_str()) {
+            info!("Tags are up to date (same hash)");
+            TagSyncOutcome::Unchanged
+        } else {
+            let texts: Vec<(String, Vec<String>)> = serde_json::from_slice::<Vec<SiteTag>>(&body)?
+                .into_iter()
+                .map(SiteTag::into_parts)
+                .filter(|(text, _)| !text.is_empty())
+                .collect();
+            let (tags, added, removed) = Self::merge(Self::fetch_data(path), texts);
+            fs::write(path, serde_json::to_string(&tags)?)?;
+            info!(?added, ?removed, "Tags have been updated");
+            TagSyncOutcome::Updated { added, removed }
+        };
+
+        let state = TagSyncState {
+            etag,
+            hash: Some(hash),
+        };
+        if let Err(err) = fs::write(&state_path, serde_json::to_string(&state)?) {
+            warn!(?err, "Unable to save the tag sync state");
+        }
+        Ok(outcome)
+    }
+
+    /// Return the site's tags with the colours of `cached`, and the texts of added and removed tags
+    fn merge(
+        cached: Vec<Self>,
+        texts: Vec<(String, Vec<String>)>,
+    ) -> (Vec<Self>, Vec<String>, Vec<String>) {
+        let mut seen = BTreeSet::new();
+        let mut added = Vec::new();
+        let tags: Vec<Self> = texts
+            .into_iter()
+            .filter(|(text, _)| seen.insert(text.clone()))
+            .map(|(text, aliases)| match cached.iter().find(|tag| tag.text == text) {
+                Some(tag) => TagData {
+                    aliases,
+                    ..tag.clone()
+                },
+                None => {
+                    added.push(text.clone());
+                    TagData {
+                        color: TagColor::from_text(&text),
+                        text,
+                        aliases,
+                    }
+                }
+            })
+            .collect();
+        let removed = cached
+            .into_iter()
+            .filter(|tag| !seen.contains(&tag.text))
+            .map(|tag| tag.text)
+            .collect();
+        (tags, added, removed)
+    }
+
     pub fn fetch_data<P: AsRef<Path>>(path: P) -> Vec<Self> {
-        // TODO: Download tags from GT and save it locally
-        //       Only re-download if hash has changed
         let file_content = match fs::read_to_string(path.as_ref()) {
             Ok(string) => string,
             Err(err) => {
@@ -176,13 +389,63 @@ impl TagData {
         }
     }
 
-    // TODO: Add custom tags generation/fetching/saving
-    pub fn fetch_custom() -> Vec<Self> {
-        todo!()
+    pub fn fetch_custom<P: AsRef<Path>>(path: P) -> Vec<CustomTag> {
+        let file_content = match fs::read_to_string(path.as_ref()) {
+            Ok(string) => string,
+            Err(err) => {
+                warn!(?err, "Unable to fetch custom tags locally; IGNORE this warning if initialising");
+                return Vec::new();
+            }
+        };
+
+        match serde_json::from_str::<Vec<CustomTag>>(&file_content) {
+            Ok(tags) => {
+                info!("Fetched custom tags locally");
+                tags
+            }
+            Err(err) => {
+                warn!(?err, "Unable to deserialise custom tags");
+                Vec::new()
+            }
+        }
     }
 
-    pub fn save_custom() {
-        todo!()
+    pub fn save_custom<P: AsRef<Path>>(path: P, tags: &[CustomTag]) {
+        let Ok(tags) = serde_json::to_string_pretty(tags) else {
+            warn!("Unable to serialise or hence save custom tags; saving aborted");
+            return;
+        };
+
+        match fs::write(path, tags) {
+            Ok(()) => info!("Saved custom tags"),
+            Err(err) => warn!(?err, "Unable to save custom tags; saving aborted"),
+        }
+    }
+
+    /// Read a shared custom tag set; unlike `fetch_custom`, failures are reported to the caller
+    pub fn import_custom<P: AsRef<Path>>(path: P) -> anyhow::Result<Vec<CustomTag>> {
+        let tags = serde_json::from_str::<Vec<CustomTag>>(&fs::read_to_string(path.as_ref())?)?;
+        info!("Imported {} custom tags", tags.len());
+        Ok(tags)
+    }
+
+    /// Merge `imported` into the custom tags with their selection, matching them by text;
+    /// imported colours and aliases take precedence, but local usage counts are kept
+    pub fn merge_custom(
+        custom_tags: &mut BTreeMap<TagData, bool>,
+        usage: &mut BTreeMap<String, u64>,
+        imported: Vec<CustomTag>,
+    ) {
+        for tag in imported {
+            let is_selected = custom_tags
+                .keys()
+                .find(|data| data.text == tag.data.text)
+                .cloned()
+                .and_then(|data| custom_tags.remove(&data))
+                .unwrap_or(false);
+            usage.entry(tag.data.text.clone()).or_insert(tag.usage);
+            custom_tags.insert(tag.data, is_selected);
+        }
     }
 }
 
@@ -236,3 +499,144 @@ impl<'a> Widget for Tag<'a> {
         )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::test_util::serve;
+
+    use super::*;
+
+    /// Serve one canned `(body, with_etag)` response per request, answering requests that carry
+    /// the matching ETag with `304 Not Modified`
+    fn serve_tags(responses: Vec<(&'static str, bool)>) -> String {
+        let (url, _) = serve(responses.len(), move |index, request| {
+            let (body, with_etag) = responses[index];
+            let etag = format!("\"{:x}\"", Sha256::digest(body.as_bytes()));
+            if request
+                .to_ascii_lowercase()
+                .contains(&format!("if-none-match: {etag}"))
+            {
+                (304, "".to_owned(), "".to_owned())
+            } else if with_etag {
+                (200, format!("ETag: {etag}\r\n"), body.to_owned())
+            } else {
+                (200, "".to_owned(), body.to_owned())
+            }
+        });
+        format!("{url}/tags.json")
+    }
+
+    #[test]
+    fn test_fuzzy_score() {
+        assert!(fuzzy_score("", "Onlyfans").is_some());
+        assert!(fuzzy_score("ofs", "Onlyfans").is_some());
+        assert!(fuzzy_score("ONLY", "Onlyfans").is_some());
+        assert_eq!(fuzzy_score("xyz", "Onlyfans"), None);
+        assert_eq!(fuzzy_score("fansonly", "Onlyfans"), None);
+        assert!(fuzzy_score("fan", "Fansly") > fuzzy_score("fan", "Onlyfans"));
+        assert!(fuzzy_score("f2f", "Friends 2 Follow") > fuzzy_score("f2f", "Fansly2f"));
+    }
+
+    #[test]
+    fn test_sync() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("tags.json");
+        fs::write(
+            &path,
+            serde_json::to_string(&[TagData {
+                text: "Onlyfans".to_owned(),
+                color: TagColor::Red,
+                aliases: Vec::new(),
+            }])
+            .unwrap(),
+        )
+        .unwrap();
+
+        let first = r#"["Onlyfans", {"name": "Fansly", "aliases": ["Fans.ly"]}]"#;
+        let second = r#"["Fansly", "Reddit"]"#;
+        let url = serve_tags(vec![(first, true), (first, true), (second, false), (second, false)]);
+        let client = Client::new();
+
+        assert_eq!(
+            TagData::sync(&client, &url, &path).unwrap(),
+            TagSyncOutcome::Updated {
+                added: vec!["Fansly".to_owned()],
+                removed: vec![],
+            }
+        );
+        let tags = TagData::fetch_data(&path);
+        assert_eq!(tags[0].color, TagColor::Red);
+        assert_eq!(tags[1].color, TagColor::from_text("Fansly"));
+        assert_eq!(tags[1].aliases, ["Fans.ly"]);
+
+        // Not modified according to the ETag
+        assert_eq!(
+            TagData::sync(&client, &url, &path).unwrap(),
+            TagSyncOutcome::Unchanged
+        );
+
+        assert_eq!(
+            TagData::sync(&client, &url, &path).unwrap(),
+            TagSyncOutcome::Updated {
+                added: vec!["Reddit".to_owned()],
+                removed: vec!["Onlyfans".to_owned()],
+            }
+        );
+
+        // Without an ETag, the content hash is compared instead
+        assert_eq!(
+            TagData::sync(&client, &url, &path).unwrap(),
+            TagSyncOutcome::Unchanged
+        );
+        assert_eq!(TagData::fetch_data(&path).len(), 2);
+    }
+
+    fn custom(text: &str, color: TagColor, usage: u64) -> CustomTag {
+        CustomTag {
+            data: TagData {
+                text: text.to_owned(),
+                color,
+                aliases: Vec::new(),
+            },
+            usage,
+        }
+    }
+
+    #[test]
+    fn test_save_custom() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("custom_tags.json");
+        let tags = vec![custom("Beach", TagColor::Teal, 3), custom("Sunset", TagColor::Red, 0)];
+        TagData::save_custom(&path, &tags);
+        assert_eq!(TagData::fetch_custom(&path), tags);
+        assert_eq!(TagData::import_custom(&path).unwrap(), tags);
+
+        // Missing or corrupt files are treated as having no custom tags, but fail imports
+        assert!(TagData::fetch_custom(dir.path().join("missing.json")).is_empty());
+        assert!(TagData::import_custom(dir.path().join("missing.json")).is_err());
+        fs::write(&path, "[{").unwrap();
+        assert!(TagData::fetch_custom(&path).is_empty());
+        assert!(TagData::import_custom(&path).is_err());
+    }
+
+    #[test]
+    fn test_merge_custom() {
+        let beach = custom("Beach", TagColor::Teal, 3);
+        let mut custom_tags = BTreeMap::from([(beach.data.clone(), true)]);
+        let mut usage = BTreeMap::from([("Beach".to_owned(), 3)]);
+        TagData::merge_custom(
+            &mut custom_tags,
+            &mut usage,
+            vec![custom("Beach", TagColor::Red, 9), custom("Sunset", TagColor::Amber, 2)],
+        );
+
+        assert_eq!(
+            custom_tags.into_iter().collect::<Vec<(TagData, bool)>>(),
+            [
+                (custom("Beach", TagColor::Red, 0).data, true),
+                (custom("Sunset", TagColor::Amber, 0).data, false),
+            ]
+        );
+        assert_eq!(usage, BTreeMap::from([("Beach".to_owned(), 3), ("Sunset".to_owned(), 2)]));
+    }
+}
@@ -0,0 +1,301 @@
+// SPDX-License-Identifier: BSD-2-Clause-Patent
+
+use std::fs;
+use std::path::Path;
+
+use bytesize::ByteSize;
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+use walkdir::WalkDir;
+
+use crate::media::{format_bitrate, format_duration, MediaInfo};
+
+pub const PLACEHOLDERS: [&str; 13] = [
+    "filename",
+    "file_count",
+    "total_size",
+    "duration",
+    "resolution",
+    "container",
+    "video",
+    "bitrate",
+    "audio",
+    "subtitles",
+    "media_info",
+    "categories",
+    "images",
+];
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Template {
+    pub name: String,
+    pub body: String,
+}
+
+/// Values substituted for the placeholders of a template
+#[derive(Debug, Clone, Default)]
+pub struct TemplateValues {
+    pub filename: String,
+    pub file_count: usize,
+    pub total_size: u64,
+    pub duration: Option<String>,
+    pub resolution: Option<String>,
+    pub container: Option<String>,
+    pub video: Option<String>,
+    pub bitrate: Option<String>,
+    pub audio: Option<String>,
+    pub subtitles: Option<String>,
+    // One line per video
+    pub media_info: Option<String>,
+    pub categories: Vec<String>,
+    // URLs of the images on the image host
+    pub image_urls: Vec<String>,
+}
+
+/// Join the distinct values, or return `None` if there are none
+fn join_distinct(values: impl Iterator<Item = Option<String>>) -> Option<String> {
+    let mut distinct: Vec<String> = Vec::new();
+    for value in values.flatten() {
+        if !distinct.contains(&value) {
+            distinct.push(value);
+        }
+    }
+    (!distinct.is_empty()).then(|| distinct.join(", "))
+}
+
+impl TemplateValues {
+    pub fn from_content<P: AsRef<Path>>(content_path: P, total_size: u64) -> Self {
+        let content_path = content_path.as_ref();
+        Self {
+            filename: content_path
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default(),
+            file_count: WalkDir::new(content_path)
+                .into_iter()
+                .filter_map(|entry| entry.ok())
+                .filter(|entry| entry.file_type().is_file())
+                .count(),
+            total_size,
+            ..Default::default()
+        }
+    }
+
+    /// Fill in the details of the videos in the content; the duration is the total and values
+    /// which differ between videos are listed
+    pub fn set_media(&mut self, media: &[(String, MediaInfo)]) {
+        let infos = || media.iter().map(|(_, info)| info);
+        self.duration = infos()
+            .filter_map(|info| info.duration)
+            .reduce(|total, duration| total + duration)
+            .map(format_duration);
+        self.resolution = join_distinct(infos().map(MediaInfo::resolution));
+        self.container = join_distinct(
+            infos().map(|info| (!info.container.is_empty()).then(|| info.container.clone())),
+        );
+        self.video = join_distinct(infos().map(MediaInfo::video_description));
+        self.bitrate = join_distinct(infos().map(|info| info.bitrate.map(format_bitrate)));
+        self.audio = join_distinct(infos().map(MediaInfo::audio_description));
+        self.subtitles = join_distinct(infos().map(MediaInfo::subtitle_description));
+        self.media_info = (!media.is_empty()).then(|| {
+            media
+                .iter()
+                .map(|(path, info)| format!("{path}: {}", info.summary()))
+                .collect::<Vec<String>>()
+                .join("\n")
+        });
+    }
+
+    fn get(&self, placeholder: &str) -> Option<String> {
+        const UNKNOWN: &str = "N/A";
+        match placeholder {
+            "filename" => Some(self.filename.clone()),
+            "file_count" => Some(self.file_count.to_string()),
+            "total_size" => Some(ByteSize(self.total_size).to_string()),
+            "duration" => Some(self.duration.as_deref().unwrap_or(UNKNOWN).to_owned()),
+            "resolution" => Some(self.resolution.as_deref().unwrap_or(UNKNOWN).to_owned()),
+            "container" => Some(self.container.as_deref().unwrap_or(UNKNOWN).to_owned()),
+            "video" => Some(self.video.as_deref().unwrap_or(UNKNOWN).to_owned()),
+            "bitrate" => Some(self.bitrate.as_deref().unwrap_or(UNKNOWN).to_owned()),
+            "audio" => Some(self.audio.as_deref().unwrap_or(UNKNOWN).to_owned()),
+            "subtitles" => Some(self.subtitles.as_deref().unwrap_or("None").to_owned()),
+            "media_info" => Some(self.media_info.as_deref().unwrap_or(UNKNOWN).to_owned()),
+            "categories" => Some(self.categories.join(", ")),
+            // Plain URLs, as the site does not accept BB code in descriptions
+            "images" => Some(self.image_urls.join("\n")),
+            _ => None,
+        }
+    }
+}
+
+impl Template {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_owned(),
+            body: "".to_owned(),
+        }
+    }
+
+    /// Replace every known `{placeholder}`; unknown ones are left untouched
+    pub fn render(&self, values: &TemplateValues) -> String {
+        let mut rendered = String::with_capacity(self.body.len());
+        let mut rest = self.body.as_str();
+        while let Some(start) = rest.find('{') {
+            rendered.push_str(&rest[..start]);
+            rest = &rest[start..];
+            match rest[1..]
+                .find(['{', '}'])
+                .filter(|&end| rest.as_bytes()[end + 1] == b'}')
+                .and_then(|end| values.get(&rest[1..end + 1]).map(|value| (end, value)))
+            {
+                Some((end, value)) => {
+                    rendered.push_str(&value);
+                    rest = &rest[end + 2..];
+                }
+                None => {
+                    rendered.push('{');
+                    rest = &rest[1..];
+                }
+            }
+        }
+        rendered.push_str(rest);
+        rendered
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Vec<Self> {
+        let file_content = match fs::read_to_string(path.as_ref()) {
+            Ok(string) => string,
+            Err(err) => {
+                warn!(?err, "Unable to load templates; IGNORE this warning if initialising");
+                return Vec::new();
+            }
+        };
+
+        match serde_json::from_str::<Vec<Self>>(&file_content) {
+            Ok(templates) => {
+                info!("Loaded templates");
+                templates
+            }
+            Err(err) => {
+                warn!(?err, "Unable to deserialise templates");
+                Vec::new()
+            }
+        }
+    }
+
+    pub fn save<P: AsRef<Path>>(path: P, templates: &[Self]) {
+        let Ok(templates) = serde_json::to_string_pretty(templates) else {
+            warn!("Unable to serialise or hence save templates; saving aborted");
+            return;
+        };
+
+        match fs::write(path, templates) {
+            Ok(()) => info!("Saved templates"),
+            Err(err) => warn!(?err, "Unable to save templates; saving aborted"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn values() -> TemplateValues {
+        TemplateValues {
+            filename: "holiday.mp4".to_owned(),
+            file_count: 3,
+            total_size: 2_000_000,
+            duration: None,
+            resolution: Some("1920x1080".to_owned()),
+            container: None,
+            video: None,
+            bitrate: None,
+            audio: None,
+            subtitles: None,
+            media_info: None,
+            categories: vec!["Amateur".to_owned(), "Solo".to_owned()],
+            image_urls: vec![
+                "https://images.example/1.png".to_owned(),
+                "https://images.example/2.png".to_owned(),
+            ],
+        }
+    }
+
+    #[test]
+    fn test_render_placeholders() {
+        let template = Template {
+            name: "test".to_owned(),
+            body: "{filename} ({file_count} files, {total_size})\n{resolution}, {duration}\n\
+                   {categories}\n{images}"
+                .to_owned(),
+        };
+        assert_eq!(
+            template.render(&values()),
+            "holiday.mp4 (3 files, 2.0 MB)\n1920x1080, N/A\nAmateur, Solo\n\
+             https://images.example/1.png\nhttps://images.example/2.png"
+        );
+    }
+
+    #[test]
+    fn test_render_valid_description() {
+        use crate::validation::{has_errors, validate_description};
+
+        let template = Template {
+            name: "test".to_owned(),
+            body: "{filename}, {total_size} in {file_count} files\n\nImages:\n{images}".to_owned(),
+        };
+        assert!(!has_errors(&validate_description(&template.render(&values()))));
+    }
+
+    #[test]
+    fn test_render_unknown_placeholders() {
+        let template = Template {
+            name: "test".to_owned(),
+            body: "{unknown} {{filename}} {filename".to_owned(),
+        };
+        assert_eq!(
+            template.render(&values()),
+            "{unknown} {holiday.mp4} {filename"
+        );
+    }
+
+    #[test]
+    fn test_set_media() {
+        use std::time::Duration;
+
+        use crate::media::{AudioStream, VideoStream};
+
+        let info = |seconds, width, height| MediaInfo {
+            duration: Some(Duration::from_secs(seconds)),
+            container: "Matroska / WebM".to_owned(),
+            video: Some(VideoStream {
+                codec: "HEVC".to_owned(),
+                width,
+                height,
+                frame_rate: Some(25.),
+            }),
+            audio: vec![AudioStream {
+                codec: "AAC".to_owned(),
+                channels: "stereo".to_owned(),
+            }],
+            ..Default::default()
+        };
+        let mut values = values();
+        values.set_media(&[
+            ("show/1.mkv".to_owned(), info(1800, 1920, 1080)),
+            ("show/2.mkv".to_owned(), info(1830, 1280, 720)),
+        ]);
+        let template = Template {
+            name: "test".to_owned(),
+            body: "{duration} | {resolution} | {video} | {audio} | {subtitles}\n{media_info}"
+                .to_owned(),
+        };
+        assert_eq!(
+            template.render(&values),
+            "01:00:30 | 1920x1080, 1280x720 | HEVC 1920x1080 25 fps, HEVC 1280x720 25 fps | \
+             AAC stereo | None\n\
+             show/1.mkv: 00:30:00 · Matroska / WebM · HEVC 1920x1080 25 fps · AAC stereo\n\
+             show/2.mkv: 00:30:30 · Matroska / WebM · HEVC 1280x720 25 fps · AAC stereo"
+        );
+    }
+}
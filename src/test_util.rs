@@ -0,0 +1,53 @@
+// SPDX-License-Identifier: BSD-2-Clause-Patent
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpListener;
+use std::thread;
+
+/// Serve `count` connections on a local port, answering each request with the status, extra
+/// header lines and body returned by `respond` for its index and text; return the server's
+/// base URL and a handle yielding the requests
+pub fn serve<F>(count: usize, mut respond: F) -> (String, thread::JoinHandle<Vec<String>>)
+where
+    F: FnMut(usize, &str) -> (u16, String, String) + Send + 'static,
+{
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let handle = thread::spawn(move || {
+        let mut requests = Vec::new();
+        for index in 0..count {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream);
+            let mut request = String::new();
+            let mut content_length = 0;
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if let Some((name, value)) = line.split_once(':') {
+                    if name.eq_ignore_ascii_case("content-length") {
+                        content_length = value.trim().parse().unwrap();
+                    }
+                }
+                request.push_str(&line);
+                if line == "\r\n" || line.is_empty() {
+                    break;
+                }
+            }
+            let mut content = vec![0; content_length];
+            reader.read_exact(&mut content).unwrap();
+            request.push_str(&String::from_utf8_lossy(&content));
+
+            let (status, headers, body) = respond(index, &request);
+            write!(
+                reader.get_mut(),
+                "HTTP/1.1 {status} Status\r\n{headers}Content-Length: {}\r\n\
+                 Connection: close\r\n\r\n{body}",
+                body.len()
+            )
+            .unwrap();
+            requests.push(request);
+        }
+        requests
+    });
+    (url, handle)
+}
@@ -1,10 +1,11 @@
 // SPDX-License-Identifier: BSD-2-Clause-Patent
 
 use std::borrow::Cow;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::sync::mpsc;
 use std::time::{SystemTime, UNIX_EPOCH};
 
+use anyhow::{ensure, Context};
 use lava_torrent::bencode::BencodeElem;
 use lava_torrent::torrent::v1::{Integer, TorrentBuilder};
 use tracing::{info, warn};
@@ -32,20 +33,10 @@ fn calculate_piece_length(mut total_length: u64) -> u64 {
     2u64.pow(total_length.ilog2()).min(2u64.pow(20))
 }
 
-pub(crate) fn create_torrent_file<P: AsRef<Path>>(
-    content_path: P,
-    sender: mpsc::Sender<DialogMessage>,
-) {
+/// Hash the content and write the torrent into the data directory, returning its path
+pub(crate) fn create_torrent<P: AsRef<Path>>(content_path: P) -> anyhow::Result<PathBuf> {
     let content_path = content_path.as_ref();
-    if !content_path.exists() {
-        warn!(?content_path, "Content path does not exist; upload aborted");
-        sender
-            .send(DialogMessage(
-                Cow::Borrowed("Content path does not exist; upload aborted"),
-                true,
-            ))
-            .unwrap();
-    }
+    ensure!(content_path.exists(), "Content path does not exist");
     let applicaton_name = format!(
         "Quick Torrent Maker 2, v{}",
         QtmVersion::get_current_version()
@@ -83,42 +74,46 @@ pub(crate) fn create_torrent_file<P: AsRef<Path>>(
         "encoding".to_owned(),
         BencodeElem::String("UTF-8".to_owned()),
     )
-    .build();
+    .build()
+    .context("Failed to create torrent")?;
 
-    match torrent {
-        Ok(torrent) => {
-            let filename = &format!("qtm2-{}.torrent", creation_time);
-            info!("{filename} has been created successfully");
-            match torrent.write_into_file(data_local_dir(filename)) {
-                Ok(_) => {
-                    sender
-                        .send(DialogMessage(
-                            Cow::Borrowed(
-                                "Torrent has been written to disk successfully\n\nUploading...",
-                            ),
-                            true, // TODO: Add uploading & turn this to false
-                        ))
-                        .unwrap();
-                    info!("{filename} has been written to disk successfully");
-                }
-                Err(err) => {
-                    warn!(?err, "Failed to write torrent to disk; upload aborted");
-                    sender.send(DialogMessage(
-                        Cow::Borrowed("Failed to write torrent to disk\n\nUpload aborted\n\nCheck log for more information."),
-                        true,
-                    )).unwrap();
-                }
-            }
-        }
+    let filename = &format!("qtm2-{}.torrent", creation_time);
+    info!("{filename} has been created successfully");
+    let path = data_local_dir(filename);
+    torrent
+        .write_into_file(&path)
+        .context("Failed to write torrent to disk")?;
+    info!("{filename} has been written to disk successfully");
+    Ok(path)
+}
 
+/// Create the torrent of the content, reporting the outcome through `sender`, and return whether
+/// it was created
+pub(crate) fn create_torrent_file<P: AsRef<Path>>(
+    content_path: P,
+    sender: mpsc::Sender<DialogMessage>,
+) -> bool {
+    match create_torrent(content_path) {
+        Ok(_) => {
+            sender
+                .send(DialogMessage(
+                    Cow::Borrowed("Torrent has been written to disk successfully\n\nUploading..."),
+                    true, // TODO: Add uploading & turn this to false
+                ))
+                .unwrap();
+            true
+        }
         Err(err) => {
-            warn!(?err, "Failed to create torrent; upload aborted");
-            sender.send(DialogMessage(
-                Cow::Borrowed(
-                    "Failed to create torrent\n\nUpload aborted\n\nCheck log for more information.",
-                ),
-                true,
-            )).unwrap();
+            warn!(?err, "Upload aborted");
+            sender
+                .send(DialogMessage(
+                    Cow::Owned(format!(
+                        "{err}\n\nUpload aborted\n\nCheck log for more information."
+                    )),
+                    true,
+                ))
+                .unwrap();
+            false
         }
     }
 }
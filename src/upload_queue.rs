@@ -0,0 +1,420 @@
+// SPDX-License-Identifier: BSD-2-Clause-Patent
+
+use std::fmt::{self, Display, Formatter};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::thread;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::Context as _;
+use eframe::egui::Context;
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use crate::draft::Draft;
+use crate::image_host::{upload_with_retry, MultipartHost};
+use crate::qtm_config::ImageHostConfig;
+use crate::torrent::create_torrent;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum JobState {
+    Queued,
+    Hashing,
+    // Hosting the images
+    Uploading { uploaded: usize, total: usize },
+    // The torrent is created and the images hosted
+    // TODO: Submit the job to the site, then hand the torrent to the client to seed
+    #[serde(alias = "Done")]
+    ReadyToUpload,
+    Failed(String),
+    Skipped,
+}
+
+impl JobState {
+    pub fn is_running(&self) -> bool {
+        matches!(self, Self::Hashing | Self::Uploading { .. })
+    }
+
+    pub fn is_retryable(&self) -> bool {
+        matches!(self, Self::Failed(_) | Self::Skipped)
+    }
+}
+
+impl Display for JobState {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Queued => write!(f, "Queued"),
+            Self::Hashing => write!(f, "Hashing"),
+            Self::Uploading { uploaded, total } => write!(f, "Uploading images {uploaded}/{total}"),
+            Self::ReadyToUpload => write!(f, "Ready to upload"),
+            Self::Failed(err) => write!(f, "Failed: {err}"),
+            Self::Skipped => write!(f, "Skipped"),
+        }
+    }
+}
+
+/// An upload waiting in, or processed by, the queue
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UploadJob {
+    // Milliseconds since the Unix epoch when the job was queued
+    pub id: u64,
+    pub draft: Draft,
+    // The preprocessed copies of the images, or the originals if not preprocessed
+    pub image_paths: Vec<PathBuf>,
+    pub state: JobState,
+    pub torrent: Option<PathBuf>,
+    pub image_urls: Vec<String>,
+}
+
+impl UploadJob {
+    pub fn new(draft: Draft, image_paths: Vec<PathBuf>) -> Self {
+        Self {
+            id: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_millis() as u64,
+            draft,
+            image_paths,
+            state: JobState::Queued,
+            torrent: None,
+            image_urls: Vec::new(),
+        }
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Vec<Self> {
+        let file_content = match fs::read_to_string(path.as_ref()) {
+            Ok(string) => string,
+            Err(err) => {
+                warn!(
+                    ?err,
+                    "Unable to load the upload queue; IGNORE this warning if initialising"
+                );
+                return Vec::new();
+            }
+        };
+
+        match serde_json::from_str::<Vec<Self>>(&file_content) {
+            Ok(mut jobs) => {
+                info!("Loaded the upload queue");
+                // The application was closed while they were running
+                for job in jobs.iter_mut().filter(|job| job.state.is_running()) {
+                    job.state = JobState::Queued;
+                }
+                jobs
+            }
+            Err(err) => {
+                warn!(?err, "Unable to deserialise the upload queue");
+                Vec::new()
+            }
+        }
+    }
+
+    pub fn save<P: AsRef<Path>>(path: P, jobs: &[Self]) {
+        let Ok(jobs) = serde_json::to_string_pretty(jobs) else {
+            warn!("Unable to serialise or hence save the upload queue; saving aborted");
+            return;
+        };
+
+        match fs::write(path, jobs) {
+            Ok(()) => info!("Saved the upload queue"),
+            Err(err) => warn!(?err, "Unable to save the upload queue; saving aborted"),
+        }
+    }
+
+    /// Hash the content into a torrent, then host the images if an image host is configured,
+    /// sending the job after every step; images hosted before the job was queued are not uploaded
+    /// again
+    fn run(
+        mut self,
+        host: Option<ImageHostConfig>,
+        sender: &mpsc::Sender<UploadJob>,
+        ctx: &Context,
+    ) {
+        let send = |job: &UploadJob| {
+            // The receiver only disappears when the application is closing
+            let _ = sender.send(job.clone());
+            ctx.request_repaint();
+        };
+        let Some(content) = self.draft.content.clone() else {
+            self.state = JobState::Failed("No content".to_owned());
+            send(&self);
+            return;
+        };
+
+        self.state = JobState::Hashing;
+        send(&self);
+        match create_torrent(content) {
+            Ok(torrent) => self.torrent = Some(torrent),
+            Err(err) => {
+                warn!(?err, "Unable to create the torrent of job {}", self.id);
+                self.state = JobState::Failed(format!("{err:#}"));
+                send(&self);
+                return;
+            }
+        }
+
+        if let Some(host) = host {
+            let host = match MultipartHost::new(host) {
+                Ok(host) => host,
+                Err(err) => {
+                    self.state = JobState::Failed(err.to_string());
+                    send(&self);
+                    return;
+                }
+            };
+            self.image_urls.clear();
+            let total = self.image_paths.len();
+            for (uploaded, path) in self.image_paths.clone().iter().enumerate() {
+                let hosted_url = self
+                    .draft
+                    .images
+                    .get(uploaded)
+                    .and_then(|image| self.draft.image_urls.get(image));
+                if let Some(url) = hosted_url {
+                    self.image_urls.push(url.clone());
+                    continue;
+                }
+                self.state = JobState::Uploading { uploaded, total };
+                send(&self);
+                match upload_with_retry(&host, path) {
+                    Ok(url) => self.image_urls.push(url),
+                    Err(err) => {
+                        warn!(?err, "Unable to upload {}", path.to_string_lossy());
+                        let filename = path.file_name().unwrap_or_default().to_string_lossy();
+                        self.state = JobState::Failed(format!("{filename}: {err}"));
+                        send(&self);
+                        return;
+                    }
+                }
+            }
+        } else {
+            self.image_urls = self
+                .draft
+                .images
+                .iter()
+                .filter_map(|image| self.draft.image_urls.get(image).cloned())
+                .collect();
+        }
+
+        info!("Finished job {}", self.id);
+        self.state = JobState::ReadyToUpload;
+        send(&self);
+    }
+}
+
+/// Copy the images into `dir`, prefixed by their index so that same-named images do not clash,
+/// and return the paths of the copies
+fn copy_images(paths: &[PathBuf], dir: &Path) -> anyhow::Result<Vec<PathBuf>> {
+    fs::create_dir_all(dir)?;
+    paths
+        .iter()
+        .enumerate()
+        .map(|(index, path)| {
+            let filename = path.file_name().unwrap_or_default().to_string_lossy();
+            let copy = dir.join(format!("{index}-{filename}"));
+            fs::copy(path, &copy)
+                .with_context(|| format!("Unable to copy {}", path.to_string_lossy()))?;
+            Ok(copy)
+        })
+        .collect()
+}
+
+/// Uploads which run one at a time in the background, saved to `path` whenever they change so
+/// that they survive restarts
+pub struct UploadQueue {
+    pub jobs: Vec<UploadJob>,
+    path: PathBuf,
+    // Holds a copy of the images of every job in a folder named after its ID, as the originals
+    // may be temporary files
+    images_dir: PathBuf,
+    // Whether a job is running on the worker thread
+    is_busy: bool,
+    channel: (mpsc::Sender<UploadJob>, mpsc::Receiver<UploadJob>),
+}
+
+impl UploadQueue {
+    pub fn new<P: AsRef<Path>>(path: P, images_dir: P) -> Self {
+        Self {
+            jobs: UploadJob::load(path.as_ref()),
+            path: path.as_ref().to_path_buf(),
+            images_dir: images_dir.as_ref().to_path_buf(),
+            is_busy: false,
+            channel: mpsc::channel(),
+        }
+    }
+
+    fn save(&self) {
+        UploadJob::save(&self.path, &self.jobs);
+    }
+
+    /// Queue a job with its images copied into the queue's own folder
+    pub fn push(&mut self, mut job: UploadJob) -> anyhow::Result<()> {
+        let dir = self.images_dir.join(job.id.to_string());
+        match copy_images(&job.image_paths, &dir) {
+            Ok(image_paths) => job.image_paths = image_paths,
+            Err(err) => {
+                let _ = fs::remove_dir_all(&dir);
+                return Err(err);
+            }
+        }
+        info!("Queued job {}", job.id);
+        self.jobs.push(job);
+        self.save();
+        Ok(())
+    }
+
+    /// Queue a failed or skipped job again
+    pub fn retry(&mut self, id: u64) {
+        if self.jobs.iter().any(|job| job.id == id && job.state.is_retryable()) {
+            self.set_state(id, JobState::Queued);
+        }
+    }
+
+    /// Skip a job which has not started yet
+    pub fn skip(&mut self, id: u64) {
+        if self.jobs.iter().any(|job| job.id == id && job.state == JobState::Queued) {
+            self.set_state(id, JobState::Skipped);
+        }
+    }
+
+    fn set_state(&mut self, id: u64, state: JobState) {
+        if let Some(job) = self.jobs.iter_mut().find(|job| job.id == id) {
+            if !job.state.is_running() {
+                job.state = state;
+                self.save();
+            }
+        }
+    }
+
+    /// Remove a job and its images unless it is running
+    pub fn remove(&mut self, id: u64) {
+        let count = self.jobs.len();
+        self.jobs
+            .retain(|job| job.id != id || job.state.is_running());
+        if self.jobs.len() == count {
+            return;
+        }
+        self.save();
+        let dir = self.images_dir.join(id.to_string());
+        if let Err(err) = fs::remove_dir_all(&dir) {
+            warn!(?err, "Unable to remove {}", dir.to_string_lossy());
+        }
+    }
+
+    pub fn pending_count(&self) -> usize {
+        self.jobs
+            .iter()
+            .filter(|job| job.state == JobState::Queued || job.state.is_running())
+            .count()
+    }
+
+    /// Apply the progress of the running job and start the next one when it is done, returning
+    /// the jobs which have just finished
+    pub fn poll(&mut self, image_host: &ImageHostConfig, ctx: &Context) -> Vec<UploadJob> {
+        let mut is_changed = false;
+        let mut finished = Vec::new();
+        for update in self.channel.1.try_iter() {
+            if !update.state.is_running() {
+                self.is_busy = false;
+            }
+            if update.state == JobState::ReadyToUpload {
+                finished.push(update.clone());
+            }
+            if let Some(job) = self.jobs.iter_mut().find(|job| job.id == update.id) {
+                *job = update;
+                is_changed = true;
+            }
+        }
+
+        if !self.is_busy {
+            if let Some(job) = self
+                .jobs
+                .iter_mut()
+                .find(|job| job.state == JobState::Queued)
+            {
+                info!("Started job {}", job.id);
+                self.is_busy = true;
+                job.state = JobState::Hashing;
+                is_changed = true;
+                let job = job.clone();
+                let host = (!image_host.endpoint.is_empty()).then(|| image_host.clone());
+                let sender = self.channel.0.clone();
+                let ctx = ctx.clone();
+                thread::spawn(move || job.run(host, &sender, &ctx));
+            }
+        }
+        if is_changed {
+            self.save();
+        }
+        finished
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_load() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("queue.json");
+        let mut jobs = vec![
+            UploadJob::new(Draft::default(), Vec::new()),
+            UploadJob::new(Draft::default(), Vec::new()),
+        ];
+        jobs[0].state = JobState::Uploading {
+            uploaded: 1,
+            total: 3,
+        };
+        jobs[1].state = JobState::Failed("Content path does not exist".to_owned());
+        UploadJob::save(&path, &jobs);
+
+        let loaded = UploadJob::load(&path);
+        assert_eq!(loaded[0].state, JobState::Queued);
+        assert_eq!(loaded[1], jobs[1]);
+
+        // Saved before the state was renamed
+        assert_eq!(
+            serde_json::from_str::<JobState>(r#""Done""#).unwrap(),
+            JobState::ReadyToUpload
+        );
+    }
+
+    #[test]
+    fn test_push_and_remove() {
+        let dir = tempfile::tempdir().unwrap();
+        let dir = dir.path();
+        let image = dir.join("image.png");
+        fs::write(&image, b"image").unwrap();
+        let mut queue = UploadQueue::new(dir.join("queue.json"), dir.join("queue"));
+
+        let job = UploadJob::new(Draft::default(), vec![image.clone(), image.clone()]);
+        let id = job.id;
+        queue.push(job).unwrap();
+        let job_dir = dir.join("queue").join(id.to_string());
+        assert_eq!(
+            queue.jobs[0].image_paths,
+            [job_dir.join("0-image.png"), job_dir.join("1-image.png")]
+        );
+        fs::remove_file(&image).unwrap();
+        assert_eq!(fs::read(&queue.jobs[0].image_paths[1]).unwrap(), b"image");
+
+        queue.remove(id);
+        assert!(queue.jobs.is_empty());
+        assert!(!job_dir.exists());
+
+        // Only jobs which have not started can be skipped
+        let mut job = UploadJob::new(Draft::default(), Vec::new());
+        job.state = JobState::ReadyToUpload;
+        let id = job.id;
+        queue.jobs.push(job);
+        queue.skip(id);
+        assert_eq!(queue.jobs[0].state, JobState::ReadyToUpload);
+        queue.jobs.clear();
+
+        // A job whose images are gone is not queued
+        assert!(queue.push(UploadJob::new(Draft::default(), vec![image])).is_err());
+        assert!(queue.jobs.is_empty());
+    }
+}
@@ -0,0 +1,338 @@
+// SPDX-License-Identifier: BSD-2-Clause-Patent
+
+use std::borrow::Cow;
+use std::path::Path;
+
+use eframe::egui::Color32;
+use strum_macros::Display;
+use walkdir::WalkDir;
+
+use crate::category::{Category, SiteCategories};
+
+pub const TITLE_MIN_LENGTH: usize = 10;
+pub const TITLE_MAX_LENGTH: usize = 255;
+pub const DESCRIPTION_MIN_LENGTH: usize = 30;
+pub const DESCRIPTION_MAX_LENGTH: usize = 65_535;
+
+const BB_TAGS: [&str; 16] = [
+    "b", "i", "u", "s", "url", "img", "quote", "code", "size", "color", "colour", "font",
+    "center", "list", "spoiler", "youtube",
+];
+const WINDOWS_ILLEGAL_CHARACTERS: [char; 9] = ['<', '>', ':', '"', '/', '\\', '|', '?', '*'];
+const WINDOWS_RESERVED_NAMES: [&str; 22] = [
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+    "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Display)]
+pub enum Severity {
+    Info,
+    Warning,
+    Error,
+}
+
+impl Severity {
+    pub const fn to_color(self) -> Color32 {
+        match self {
+            Severity::Info => Color32::from_rgb(96, 125, 139),
+            Severity::Warning => Color32::from_rgb(255, 152, 0),
+            Severity::Error => Color32::from_rgb(244, 67, 54),
+        }
+    }
+
+    pub const fn to_symbol(self) -> &'static str {
+        match self {
+            Severity::Info => "ℹ",
+            Severity::Warning => "⚠",
+            Severity::Error => "✗",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Issue {
+    pub severity: Severity,
+    pub message: Cow<'static, str>,
+}
+
+impl Issue {
+    fn new(severity: Severity, message: impl Into<Cow<'static, str>>) -> Self {
+        Self {
+            severity,
+            message: message.into(),
+        }
+    }
+}
+
+pub fn has_errors(issues: &[Issue]) -> bool {
+    issues.iter().any(|issue| issue.severity == Severity::Error)
+}
+
+pub fn validate_title(title: &str) -> Vec<Issue> {
+    let mut issues = Vec::new();
+    let trimmed = title.trim();
+    if trimmed.is_empty() {
+        issues.push(Issue::new(Severity::Error, "Title is required"));
+        return issues;
+    }
+
+    let length = trimmed.chars().count();
+    if length < TITLE_MIN_LENGTH {
+        issues.push(Issue::new(
+            Severity::Warning,
+            format!("Title is shorter than {TITLE_MIN_LENGTH} characters; please be descriptive"),
+        ));
+    }
+    if length > TITLE_MAX_LENGTH {
+        issues.push(Issue::new(
+            Severity::Error,
+            format!("Title is longer than {TITLE_MAX_LENGTH} characters"),
+        ));
+    }
+    if trimmed.chars().any(char::is_control) {
+        issues.push(Issue::new(
+            Severity::Error,
+            "Title contains control characters",
+        ));
+    }
+    if let Some(tag) = find_markup(trimmed) {
+        issues.push(Issue::new(
+            Severity::Error,
+            format!("HTML/BB code is not allowed: {tag}"),
+        ));
+    }
+    if is_all_caps(trimmed) {
+        issues.push(Issue::new(Severity::Warning, "Title is written in all caps"));
+    }
+    if has_duplicated_whitespace(title) || title.trim() != title {
+        issues.push(Issue::new(
+            Severity::Info,
+            "Title contains duplicated, leading or trailing whitespace",
+        ));
+    }
+    issues
+}
+
+pub fn validate_description(description: &str) -> Vec<Issue> {
+    let mut issues = Vec::new();
+    if description.trim().is_empty() {
+        issues.push(Issue::new(Severity::Error, "Description is required"));
+        return issues;
+    }
+
+    let length = description.trim().chars().count();
+    if length < DESCRIPTION_MIN_LENGTH {
+        issues.push(Issue::new(
+            Severity::Warning,
+            format!("Description is shorter than {DESCRIPTION_MIN_LENGTH} characters"),
+        ));
+    }
+    if length > DESCRIPTION_MAX_LENGTH {
+        issues.push(Issue::new(
+            Severity::Error,
+            format!("Description is longer than {DESCRIPTION_MAX_LENGTH} characters"),
+        ));
+    }
+    if description
+        .chars()
+        .any(|c| c.is_control() && c != '\n' && c != '\r' && c != '\t')
+    {
+        issues.push(Issue::new(
+            Severity::Error,
+            "Description contains control characters",
+        ));
+    }
+    if let Some(tag) = find_markup(description) {
+        issues.push(Issue::new(
+            Severity::Error,
+            format!("HTML/BB code is not allowed: {tag}"),
+        ));
+    }
+    if let Some(url) = find_url(description) {
+        issues.push(Issue::new(
+            Severity::Warning,
+            format!("Description contains a URL: {url}"),
+        ));
+    }
+    if has_duplicated_whitespace(description) {
+        issues.push(Issue::new(
+            Severity::Info,
+            "Description contains duplicated whitespace or blank lines",
+        ));
+    }
+    issues
+}
+
+/// Check that every selected category is still offered by the site
+///
+/// Only submitting to the site needs the categories' form values, so the issues are warnings.
+pub fn validate_categories(
+    categories: &[Category],
+    site_categories: &SiteCategories,
+) -> Vec<Issue> {
+    let mut selected = categories.iter().filter(|category| **category != Category::None);
+    if !site_categories.is_available() {
+        return selected
+            .next()
+            .map(|_| {
+                Issue::new(
+                    Severity::Warning,
+                    "The site's categories have not been fetched yet; \
+                     connect to the site before submitting",
+                )
+            })
+            .into_iter()
+            .collect();
+    }
+    selected
+        .filter(|category| site_categories.id_of(**category).is_none())
+        .map(|category| {
+            Issue::new(
+                Severity::Warning,
+                format!("\"{category}\" no longer exists on the site; please choose another one"),
+            )
+        })
+        .collect()
+}
+
+/// Check the names of the content and every file within it
+pub fn validate_content<P: AsRef<Path>>(content_path: P) -> Vec<Issue> {
+    WalkDir::new(content_path)
+        .into_iter()
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            validate_filename(&name).map(|reason| {
+                Issue::new(Severity::Error, format!("\"{name}\" {reason}"))
+            })
+        })
+        .collect()
+}
+
+/// Return the reason why `name` is not a valid filename on Windows (if any)
+pub fn validate_filename(name: &str) -> Option<&'static str> {
+    if name
+        .chars()
+        .any(|c| c.is_control() || WINDOWS_ILLEGAL_CHARACTERS.contains(&c))
+    {
+        return Some("contains characters that are illegal on Windows");
+    }
+    if name.ends_with('.') || name.ends_with(' ') {
+        return Some("ends with a dot or a space");
+    }
+    let stem = name.split('.').next().unwrap_or_default().trim_end();
+    if WINDOWS_RESERVED_NAMES
+        .iter()
+        .any(|reserved| reserved.eq_ignore_ascii_case(stem))
+    {
+        return Some("is a reserved name on Windows");
+    }
+    None
+}
+
+fn is_all_caps(text: &str) -> bool {
+    let letters: Vec<char> = text.chars().filter(|c| c.is_alphabetic()).collect();
+    letters.len() >= 4 && letters.iter().all(|c| c.is_uppercase())
+}
+
+fn has_duplicated_whitespace(text: &str) -> bool {
+    text.contains("  ") || text.contains("\n\n\n") || text.contains("\r\n\r\n\r\n")
+}
+
+/// Return the first HTML or BB tag found in `text` (if any)
+fn find_markup(text: &str) -> Option<&str> {
+    [('<', '>'), ('[', ']')]
+        .into_iter()
+        .filter_map(|(open, close)| {
+            text.match_indices(open).find_map(|(start, _)| {
+                let end = start + text[start..].find(close)?;
+                let inner = &text[start + 1..end];
+                let name = inner
+                    .trim_start_matches('/')
+                    .split(|c: char| c.is_whitespace() || c == '=' || c == '/')
+                    .next()
+                    .unwrap_or_default();
+                let is_tag = if open == '<' {
+                    !name.is_empty()
+                        && name.starts_with(|c: char| c.is_ascii_alphabetic())
+                        && name.chars().all(|c| c.is_ascii_alphanumeric())
+                } else {
+                    BB_TAGS.iter().any(|tag| tag.eq_ignore_ascii_case(name)) || name == "*"
+                };
+                is_tag.then(|| (start, &text[start..=end]))
+            })
+        })
+        .min_by_key(|(start, _)| *start)
+        .map(|(_, tag)| tag)
+}
+
+fn find_url(text: &str) -> Option<&str> {
+    text.split_whitespace().find(|word| {
+        let word = word.to_ascii_lowercase();
+        word.contains("http://") || word.contains("https://") || word.starts_with("www.")
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn severities(issues: &[Issue]) -> Vec<Severity> {
+        issues.iter().map(|issue| issue.severity).collect()
+    }
+
+    #[test]
+    fn test_title() {
+        assert_eq!(severities(&validate_title(" \n ")), [Severity::Error]);
+        assert!(validate_title("A descriptive title").is_empty());
+        assert_eq!(severities(&validate_title("Short")), [Severity::Warning]);
+        assert_eq!(
+            severities(&validate_title("A VERY LOUD TITLE")),
+            [Severity::Warning]
+        );
+        assert_eq!(
+            severities(&validate_title("A title with  spaces")),
+            [Severity::Info]
+        );
+        assert!(has_errors(&validate_title("A <b>bold</b> title")));
+        assert!(has_errors(&validate_title("A [b]bold[/b] title")));
+        assert!(!has_errors(&validate_title("A title [2023] <3")));
+    }
+
+    #[test]
+    fn test_description() {
+        assert!(has_errors(&validate_description("\n\n\t")));
+        assert!(!has_errors(&validate_description(
+            "A long enough description of the content\nwith a second line"
+        )));
+        assert!(has_errors(&validate_description(
+            "A long enough description with [url=https://example.com]a link[/url]"
+        )));
+        assert_eq!(
+            severities(&validate_description(
+                "A long enough description; see https://example.com"
+            )),
+            [Severity::Warning]
+        );
+    }
+
+    #[test]
+    fn test_categories() {
+        let categories = [Category::Amateur, Category::Solo, Category::None];
+        assert_eq!(
+            severities(&validate_categories(&categories, &SiteCategories::unavailable())),
+            [Severity::Warning]
+        );
+        assert!(validate_categories(&[Category::None], &SiteCategories::unavailable()).is_empty());
+    }
+
+    #[test]
+    fn test_filename() {
+        assert_eq!(validate_filename("video.mp4"), None);
+        assert!(validate_filename("what?.mp4").is_some());
+        assert!(validate_filename("a: b").is_some());
+        assert!(validate_filename("trailing.").is_some());
+        assert!(validate_filename("con.txt").is_some());
+        assert_eq!(validate_filename("console.txt"), None);
+    }
+}
@@ -0,0 +1,257 @@
+// SPDX-License-Identifier: BSD-2-Clause-Patent
+
+use std::path::PathBuf;
+use std::sync::mpsc;
+
+use bytesize::ByteSize;
+use eframe::egui;
+use eframe::egui::{
+    pos2, vec2, widgets, Align, Color32, ColorImage, Context, Frame, Key, Layout, Margin, Modifiers,
+    Rect, Rounding, Sense, TextureHandle, TextureOptions, Vec2,
+};
+use image::imageops::FilterType;
+use tracing::warn;
+
+use crate::image::Image;
+
+const MIN_ZOOM: f32 = 0.05;
+const MAX_ZOOM: f32 = 16.;
+// Scrolled points per doubling of the zoom
+const ZOOM_SPEED: f32 = 200.;
+
+type DecodedImage = (PathBuf, anyhow::Result<ColorImage>);
+
+pub enum ViewerAction {
+    Previous,
+    Next,
+    MoveUp,
+    MoveDown,
+    Remove,
+    Close,
+}
+
+/// A window showing one of the images at full resolution
+pub struct ImageViewer {
+    pub index: usize,
+    // Relative to the original dimensions; `None` fits the image to the window
+    zoom: Option<f32>,
+    offset: Vec2,
+    path: PathBuf,
+    dimensions: Option<(u32, u32)>,
+    texture_handle: Option<TextureHandle>,
+    channel: (mpsc::Sender<DecodedImage>, mpsc::Receiver<DecodedImage>),
+}
+
+impl ImageViewer {
+    pub fn new(index: usize) -> Self {
+        Self {
+            index,
+            zoom: None,
+            offset: Vec2::ZERO,
+            path: PathBuf::new(),
+            dimensions: None,
+            texture_handle: None,
+            channel: mpsc::channel(),
+        }
+    }
+
+    /// Decode the image at full resolution in the background, unless it is already shown
+    fn load(&mut self, image: &Image, ctx: &Context) {
+        if self.path == image.path {
+            return;
+        }
+        self.path = image.path.clone();
+        self.zoom = None;
+        self.offset = Vec2::ZERO;
+        self.texture_handle = None;
+        self.dimensions = image::image_dimensions(&image.path).ok();
+        // Animations are only decoded downscaled; they are shown as they are
+        if !image.animation.is_empty() {
+            return;
+        }
+
+        let path = image.path.clone();
+        let max_side = ctx.input(|i| i.max_texture_side) as u32;
+        let sender = self.channel.0.clone();
+        let ctx = ctx.clone();
+        rayon::spawn(move || {
+            let result = (|| {
+                let image = image::io::Reader::open(&path)?
+                    .with_guessed_format()?
+                    .decode()?;
+                let image = if image.width() > max_side || image.height() > max_side {
+                    image.resize(max_side, max_side, FilterType::Triangle)
+                } else {
+                    image
+                };
+                let image = image.to_rgba8();
+                Ok(ColorImage::from_rgba_unmultiplied(
+                    [image.width() as _, image.height() as _],
+                    image.as_flat_samples().as_slice(),
+                ))
+            })();
+            // The receiver only disappears when the viewer is closed
+            let _ = sender.send((path, result));
+            ctx.request_repaint();
+        });
+    }
+
+    fn poll(&mut self, ctx: &Context) {
+        for (path, result) in self.channel.1.try_iter() {
+            if path != self.path {
+                continue;
+            }
+            match result {
+                Ok(colour_image) => {
+                    self.texture_handle = Some(ctx.load_texture(
+                        format!("{}#full", path.to_string_lossy()),
+                        colour_image,
+                        TextureOptions::LINEAR,
+                    ))
+                }
+                Err(err) => warn!(?err, "Unable to load the full image; showing the preview"),
+            }
+        }
+    }
+
+    pub fn show(&mut self, ctx: &Context, images: &[Image]) -> Option<ViewerAction> {
+        let image = images.get(self.index)?;
+        self.load(image, ctx);
+        self.poll(ctx);
+
+        let mut action = None;
+        egui::Window::new("image_viewer")
+            .frame(
+                Frame::window(&ctx.style())
+                    .rounding(Rounding::same(10.))
+                    .inner_margin(Margin::same(10.)),
+            )
+            .fixed_size(ctx.screen_rect().size() * 0.8)
+            .title_bar(false)
+            .drag_bounds(ctx.screen_rect())
+            .show(ctx, |ui| {
+                let texture_handle = self
+                    .texture_handle
+                    .as_ref()
+                    .or_else(|| image.current_texture(ctx));
+                let size = match (self.dimensions, texture_handle) {
+                    (Some((width, height)), _) => vec2(width as f32, height as f32),
+                    (None, Some(texture_handle)) => texture_handle.size_vec2(),
+                    (None, None) => Vec2::ZERO,
+                };
+
+                ui.horizontal(|ui| {
+                    ui.strong(&image.filename);
+                    ui.label(format!(
+                        "{}x{} · {}",
+                        size.x,
+                        size.y,
+                        ByteSize(image.size)
+                    ));
+                    ui.with_layout(Layout::right_to_left(Align::Center), |ui| {
+                        ui.label(format!("{} / {}", self.index + 1, images.len()));
+                    });
+                });
+
+                let (rect, response) = ui.allocate_exact_size(
+                    ui.available_size() - vec2(0., 30.),
+                    Sense::click_and_drag(),
+                );
+                let fit = if size.x > 0. && size.y > 0. {
+                    (rect.width() / size.x).min(rect.height() / size.y)
+                } else {
+                    1.
+                };
+                let zoom = self.zoom.unwrap_or(fit);
+                if response.double_clicked() {
+                    self.zoom = if self.zoom.is_none() { Some(1.) } else { None };
+                    self.offset = Vec2::ZERO;
+                }
+                if response.dragged() {
+                    self.offset += response.drag_delta();
+                    self.zoom = Some(zoom);
+                }
+                if let Some(pointer) = response.hover_pos() {
+                    let scroll = ui.input(|i| i.scroll_delta.y);
+                    if scroll != 0. {
+                        let new_zoom =
+                            (zoom * (scroll / ZOOM_SPEED).exp2()).clamp(MIN_ZOOM, MAX_ZOOM);
+                        // Keep the point under the pointer in place
+                        let anchor = pointer - rect.center();
+                        self.offset = anchor + (self.offset - anchor) * (new_zoom / zoom);
+                        self.zoom = Some(new_zoom);
+                    }
+                }
+
+                let zoom = self.zoom.unwrap_or(fit);
+                match texture_handle {
+                    Some(texture_handle) => ui.painter_at(rect).image(
+                        texture_handle.id(),
+                        Rect::from_center_size(rect.center() + self.offset, size * zoom),
+                        Rect::from_min_max(pos2(0., 0.), pos2(1., 1.)),
+                        Color32::WHITE,
+                    ),
+                    None => {
+                        ui.put(rect, widgets::Spinner::new());
+                    }
+                }
+
+                ui.horizontal(|ui| {
+                    let button =
+                        |text: &str| widgets::Button::new(text).rounding(Rounding::same(10.));
+                    if ui.add_enabled(self.index > 0, button("◀")).clicked() {
+                        action = Some(ViewerAction::Previous);
+                    }
+                    if ui.add_enabled(self.index + 1 < images.len(), button("▶")).clicked() {
+                        action = Some(ViewerAction::Next);
+                    }
+                    ui.separator();
+                    if ui.add(button("Fit")).clicked() {
+                        self.zoom = None;
+                        self.offset = Vec2::ZERO;
+                    }
+                    if ui.add(button("1:1")).clicked() {
+                        self.zoom = Some(1.);
+                        self.offset = Vec2::ZERO;
+                    }
+                    ui.label(format!("{:.0}%", zoom * 100.));
+                    ui.separator();
+                    if ui
+                        .add_enabled(self.index > 0, button("↑"))
+                        .on_hover_text("Move up")
+                        .clicked()
+                    {
+                        action = Some(ViewerAction::MoveUp);
+                    }
+                    if ui
+                        .add_enabled(self.index + 1 < images.len(), button("↓"))
+                        .on_hover_text("Move down")
+                        .clicked()
+                    {
+                        action = Some(ViewerAction::MoveDown);
+                    }
+                    if ui.add(button("✗")).on_hover_text("Remove").clicked() {
+                        action = Some(ViewerAction::Remove);
+                    }
+                    ui.with_layout(Layout::right_to_left(Align::Center), |ui| {
+                        if ui.add_sized(vec2(100., 20.), button("Close")).clicked() {
+                            action = Some(ViewerAction::Close);
+                        }
+                    });
+                });
+            });
+
+        ctx.input_mut(|i| {
+            if i.consume_key(Modifiers::NONE, Key::ArrowLeft) && self.index > 0 {
+                action = Some(ViewerAction::Previous);
+            } else if i.consume_key(Modifiers::NONE, Key::ArrowRight)
+                && self.index + 1 < images.len()
+            {
+                action = Some(ViewerAction::Next);
+            } else if i.consume_key(Modifiers::NONE, Key::Escape) {
+                action = Some(ViewerAction::Close);
+            }
+        });
+        action
+    }
+}